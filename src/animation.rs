@@ -0,0 +1,102 @@
+use crate::systems::Vec2;
+
+pub const SNAP_TIME: f32 = 0.12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardAnimation {
+    pub value: u8,
+    pub from: Vec2,
+    pub elapsed: f32,
+    pub duration: f32
+}
+
+impl CardAnimation {
+    pub fn new(value: u8, from: Vec2, duration: f32) -> Self {
+        Self {
+            value,
+            from,
+            elapsed: 0.0,
+            duration
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    // Eases out so the card decelerates into its resting position
+    pub fn position(&self, to: Vec2) -> Vec2 {
+        let t = (self.elapsed / self.duration).min(1.0);
+        let eased = 1.0 - (1.0 - t) * (1.0 - t);
+        self.from + (to - self.from) * eased
+    }
+}
+
+// Seconds between each card leaving the foundations
+const CASCADE_LAUNCH_TIME: f32 = 0.15;
+const CASCADE_GRAVITY: f32 = -2400.0;
+// Share of its speed a card keeps each time it hits the floor
+const CASCADE_BOUNCE: f32 = 0.75;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CascadeCard {
+    pub value: u8,
+    pub pos: Vec2,
+    velocity: Vec2
+}
+
+// The win celebration, cards leave the foundations one at a time and bounce along the bottom
+// of the screen until they're off the side. Only copies fly, the foundations aren't touched
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinCascade {
+    pub cards: Vec<CascadeCard>,
+    // Still to launch, the last goes next
+    waiting: Vec<(u8, Vec2)>,
+    until_launch: f32,
+    card_size: Vec2,
+    // Half the board's width and height in world units, cards fall to the bottom and leave at the sides
+    bounds: Vec2
+}
+
+impl WinCascade {
+    // 'launches' in the order they leave
+    pub fn new(mut launches: Vec<(u8, Vec2)>, card_size: Vec2, bounds: Vec2) -> Self {
+        launches.reverse();
+        Self {
+            cards: vec![],
+            waiting: launches,
+            until_launch: 0.0,
+            card_size,
+            bounds
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.until_launch -= dt;
+        if self.until_launch <= 0.0 {
+            if let Some((value, pos)) = self.waiting.pop() {
+                // Spread out by value so each card takes its own path
+                let direction = if value % 2 == 0 { -1.0 } else { 1.0 };
+                let velocity = Vec2::new(direction * (300.0 + (value % 7) as f32 * 60.0), 200.0 + (value % 5) as f32 * 80.0);
+                self.cards.push(CascadeCard { value, pos, velocity });
+            }
+            self.until_launch = CASCADE_LAUNCH_TIME;
+        }
+
+        let floor = -self.bounds.y + self.card_size.y / 2.0;
+        let edge = self.bounds.x + self.card_size.x / 2.0;
+        for card in self.cards.iter_mut() {
+            card.velocity.y += CASCADE_GRAVITY * dt;
+            card.pos += card.velocity * dt;
+            if card.pos.y < floor {
+                card.pos.y = floor;
+                card.velocity.y = -card.velocity.y * CASCADE_BOUNCE;
+            }
+        }
+        self.cards.retain(|card| card.pos.x.abs() < edge);
+    }
+
+    pub fn finished(&self) -> bool {
+        self.waiting.is_empty() && self.cards.is_empty()
+    }
+}
@@ -0,0 +1,191 @@
+use std::io::Cursor;
+use std::path::Path;
+use anyhow::*;
+use instant::Duration;
+use rodio::{Decoder, Sink, OutputStreamHandle, Source, buffer::SamplesBuffer, source::SineWave};
+
+// Formats rodio is built to decode with its default features
+const SUPPORTED_EXTENSIONS: [&str; 4] = ["ogg", "wav", "flac", "mp3"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sound {
+    PickUp,
+    Place,
+    Invalid,
+    Flip
+}
+
+// Fully decoded samples, so playing a sound never has to decode again
+struct SoundData {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>
+}
+
+impl SoundData {
+    fn source(&self) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples.clone())
+    }
+}
+
+pub struct Audio {
+    stream_handle: Option<OutputStreamHandle>,
+    sounds: Vec<SoundData>,
+    // Sounds still playing, kept so 'shutdown' can stop them
+    sinks: Vec<Sink>
+}
+
+impl Audio {
+    pub fn new(stream_handle: OutputStreamHandle) -> Result<Self> {
+        let sounds = vec![
+            Audio::embedded(Sound::PickUp)?,
+            Audio::embedded(Sound::Place)?,
+            Audio::embedded(Sound::Invalid)?,
+            Audio::embedded(Sound::Flip)?
+        ];
+
+        Ok(Self {
+            stream_handle: Some(stream_handle),
+            sounds,
+            sinks: vec![]
+        })
+    }
+
+    // Each file that can't be read or decoded is logged and replaced by the embedded sound
+    pub fn load_from_paths(stream_handle: OutputStreamHandle, pick_up: &Path, place: &Path) -> Result<Self> {
+        let sounds = vec![
+            Audio::from_path_or_embedded(Sound::PickUp, pick_up)?,
+            Audio::from_path_or_embedded(Sound::Place, place)?,
+            Audio::embedded(Sound::Invalid)?,
+            Audio::embedded(Sound::Flip)?
+        ];
+
+        Ok(Self {
+            stream_handle: Some(stream_handle),
+            sounds,
+            sinks: vec![]
+        })
+    }
+
+    fn embedded(sound: Sound) -> Result<SoundData> {
+        match sound {
+            Sound::PickUp => Audio::decode("pick_up_card.ogg", include_bytes!("aud/pick_up_card.ogg").to_vec()),
+            Sound::Place => Audio::decode("place_card.ogg", include_bytes!("aud/place_card.ogg").to_vec()),
+            Sound::Flip => Audio::decode("flip.ogg", include_bytes!("aud/flip.ogg").to_vec()),
+            // There's no file for this one, a short low tone is enough to say no
+            Sound::Invalid => Ok(Audio::tone(196.0, Duration::from_millis(150)))
+        }
+    }
+
+    fn tone(frequency: f32, duration: Duration) -> SoundData {
+        let wave = SineWave::new(frequency).take_duration(duration).amplify(0.2);
+        SoundData {
+            channels: wave.channels(),
+            sample_rate: wave.sample_rate(),
+            samples: wave.collect()
+        }
+    }
+
+    fn from_path_or_embedded(sound: Sound, path: &Path) -> Result<SoundData> {
+        match Audio::from_path(path) {
+            std::result::Result::Ok(source) => Ok(source),
+            Err(e) => {
+                log::warn!("{:?}, using the default {:?} sound", e, sound);
+                Audio::embedded(sound)
+            }
+        }
+    }
+
+    fn from_path(path: &Path) -> Result<SoundData> {
+        let name = path.display().to_string();
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension {
+            Some(extension) if SUPPORTED_EXTENSIONS.contains(&extension.as_str()) => {},
+            _ => bail!("Sound '{}' isn't one of the supported formats {:?}", name, SUPPORTED_EXTENSIONS)
+        }
+
+        let bytes = std::fs::read(path).with_context(|| format!("Couldn't read sound '{}'", name))?;
+        Audio::decode(&name, bytes)
+    }
+
+    // Used when there is no output device or the sounds failed to load,
+    // every call to 'play' is then a no-op
+    pub fn silent() -> Self {
+        Self {
+            stream_handle: None,
+            sounds: vec![],
+            sinks: vec![]
+        }
+    }
+
+    fn decode(name: &str, bytes: Vec<u8>) -> Result<SoundData> {
+        let decoder = Decoder::new(Cursor::new(bytes))
+            .with_context(|| format!("Couldn't decode sound '{}'", name))?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let samples: Vec<f32> = decoder.convert_samples().collect();
+
+        if samples.is_empty() {
+            bail!("Sound '{}' contains no samples", name);
+        }
+
+        Ok(SoundData { channels, sample_rate, samples })
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        self.play_at_speed(sound, 1.0);
+    }
+
+    // Faster is also higher, 1.0 plays the sound as it is
+    pub fn play_at_speed(&mut self, sound: Sound, speed: f32) {
+        let Some(stream_handle) = &self.stream_handle else { return };
+        let Some(sound) = self.sounds.get(sound as usize) else { return };
+
+        match Sink::try_new(stream_handle) {
+            std::result::Result::Ok(sink) => {
+                sink.append(sound.source().speed(speed));
+                self.sinks.retain(|sink| !sink.empty());
+                self.sinks.push(sink);
+            },
+            Err(e) => log::warn!("Couldn't play sound: {:?}", e)
+        }
+    }
+
+    // Stops everything playing and lets go of the output, afterwards this is the same
+    // as 'silent'. Safe to call more than once
+    pub fn shutdown(&mut self) {
+        for sink in self.sinks.drain(..) {
+            sink.stop();
+        }
+        self.stream_handle = None;
+        self.sounds.clear();
+    }
+}
+
+impl Drop for Audio {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_embedded_sound_decodes() {
+        for sound in [Sound::PickUp, Sound::Place, Sound::Invalid, Sound::Flip] {
+            assert!(Audio::embedded(sound).is_ok(), "{:?}", sound);
+        }
+    }
+
+    #[test]
+    fn the_flip_is_a_short_snap() {
+        let flip = Audio::embedded(Sound::Flip).unwrap();
+        let place = Audio::embedded(Sound::Place).unwrap();
+        assert_eq!(flip.sample_rate, 44100);
+        assert!(flip.samples.len() < place.samples.len());
+    }
+}
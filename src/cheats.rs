@@ -0,0 +1,66 @@
+use crate::systems::GameState;
+
+impl GameState {
+    // Turns every card of every column face up, to look for a way through a lost game.
+    // What was shown before is kept for 'hide_down_cards'
+    pub fn reveal_all(&mut self) {
+        if self.shown_before_reveal.is_none() {
+            self.shown_before_reveal = Some(self.tableaux.iter()
+                .map(|tableau| (tableau.cards.len(), tableau.shown_cards))
+                .collect());
+        }
+        for tableau in self.tableaux.iter_mut() {
+            tableau.shown_cards = tableau.cards.len() as u8;
+            tableau.calculate_card_quads();
+        }
+    }
+
+    // Puts back what 'reveal_all' turned up. A column that has changed since can't be put
+    // back as it was, it only shows the run on top of it, which is all normal play can leave face up
+    pub fn hide_down_cards(&mut self) {
+        let before = self.shown_before_reveal.take().unwrap_or_default();
+        for t in 0..self.tableaux.len() {
+            let len = self.tableaux[t].cards.len();
+            let shown = match before.get(t) {
+                Some((before_len, shown)) if *before_len == len => *shown as usize,
+                _ => self.longest_run(t).max(len.min(1))
+            };
+            let tableau = &mut self.tableaux[t];
+            tableau.shown_cards = shown as u8;
+            tableau.calculate_card_quads();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::Audio;
+    use crate::moves::Move;
+    use crate::rules::Rules;
+    use crate::systems::GameState;
+
+    fn shown_counts(game: &GameState) -> Vec<u8> {
+        game.tableaux.iter().map(|tableau| tableau.shown_cards).collect()
+    }
+
+    #[test]
+    fn hiding_puts_back_what_reveal_all_turned_up() {
+        let mut game = GameState::new_with_seed(Audio::silent(), Rules::default(), 2);
+        let before = shown_counts(&game);
+        game.reveal_all();
+        assert_eq!(game.hidden_card_count(), 0);
+        game.hide_down_cards();
+        assert_eq!(shown_counts(&game), before);
+    }
+
+    #[test]
+    fn a_column_moved_from_while_revealed_shows_its_run() {
+        let mut game = GameState::new_with_seed(Audio::silent(), Rules::default(), 0);
+        game.reveal_all();
+        // Seed 0 deals the A♦ on top of the last column, with the 5♥ under it
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        game.hide_down_cards();
+        assert_eq!(game.tableaux[6].shown_cards, 1);
+        assert_eq!(game.tableaux[0].shown_cards, 1);
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use winit::event::VirtualKeyCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    ReturnCard,
+    CycleTalon,
+    Undo,
+    PlayTalon,
+    // Shows the next suggested move each press
+    Hint
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    bindings: HashMap<VirtualKeyCode, Action>
+}
+
+impl KeyBindings {
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    pub fn bind(&mut self, key: VirtualKeyCode, action: Action) {
+        self.bindings.insert(key, action);
+    }
+
+    pub fn unbind(&mut self, key: VirtualKeyCode) {
+        self.bindings.remove(&key);
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: HashMap::from([
+                (VirtualKeyCode::Space, Action::ReturnCard),
+                (VirtualKeyCode::Escape, Action::ReturnCard),
+                (VirtualKeyCode::Tab, Action::CycleTalon),
+                (VirtualKeyCode::Z, Action::Undo),
+                (VirtualKeyCode::T, Action::PlayTalon),
+                (VirtualKeyCode::H, Action::Hint)
+            ])
+        }
+    }
+}
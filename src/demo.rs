@@ -0,0 +1,73 @@
+use rand::Rng;
+
+use crate::moves::Move;
+use crate::systems::{GameState, PileRef, Vec2};
+
+// Seconds without input before the demo starts playing
+pub const DEMO_IDLE_TIME: f32 = 30.0;
+// Seconds between demo moves, long enough for each to be followed
+pub const DEMO_MOVE_TIME: f32 = 0.6;
+
+impl GameState {
+    // Plays a random legal move once the player has been idle long enough, call every frame.
+    // Stops by itself once the game is won or only the stock is left to cycle through
+    pub fn step_demo(&mut self) {
+        if !self.demo_enabled || self.idle_time < DEMO_IDLE_TIME || !self.hand.cards.is_empty() || self.is_won() {
+            return;
+        }
+        if self.idle_time - self.demo_played_at < DEMO_MOVE_TIME {
+            return;
+        }
+
+        let moves = self.legal_moves();
+        if moves.iter().all(|mv| matches!(mv, Move::FlipStock | Move::RecycleTalon)) {
+            // A whole pass through the stock with nothing to play means it never will be
+            self.demo_stock_moves += 1;
+            if self.demo_stock_moves > self.stock.cards.len() + self.talon.cards.len() + 1 {
+                return;
+            }
+        } else {
+            self.demo_stock_moves = 0;
+        }
+        if moves.is_empty() {
+            return;
+        }
+
+        let mv = moves[self.demo_rng.gen_range(0..moves.len())];
+        let (values, from) = self.moved_cards(&mv);
+        if self.apply_move(&mv).is_ok() {
+            self.snap_cards(&values, from);
+        }
+        self.demo_played_at = self.idle_time;
+    }
+
+    pub fn demo_playing(&self) -> bool {
+        self.demo_enabled && self.idle_time >= DEMO_IDLE_TIME
+    }
+
+    // Cards a move will pick up and where they start from, so they can be slid over
+    pub(crate) fn moved_cards(&self, mv: &Move) -> (Vec<u8>, Vec2) {
+        match *mv {
+            Move::FlipStock => {
+                let count = self.rules.draw_count.min(self.stock.cards.len());
+                let drawn = &self.stock.cards[self.stock.cards.len() - count..];
+                (drawn.iter().map(|card| card.value).collect(), self.stock.quad.pos)
+            },
+            Move::RecycleTalon => (vec![], self.talon.quad.pos),
+            Move::TalonToTableau { .. } | Move::TalonToFoundation { .. } => {
+                (vec![self.talon.cards[0].value], self.talon_top_quad().pos)
+            },
+            Move::TableauToTableau { from, start, .. } => {
+                let run = &self.tableaux[from].cards[start..];
+                (run.iter().map(|card| card.value).collect(), self.center_of(PileRef::Tableau(from), Some(start)))
+            },
+            Move::TableauToFoundation { from, .. } => {
+                let card = self.tableaux[from].cards.last().unwrap();
+                (vec![card.value], self.center_of(PileRef::Tableau(from), None))
+            },
+            Move::FoundationToTableau { from, .. } => {
+                (vec![self.foundations[from].cards[0].value], self.foundations[from].quad.pos)
+            }
+        }
+    }
+}
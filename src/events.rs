@@ -0,0 +1,21 @@
+use crate::moves::Move;
+use crate::systems::Suit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidReason {
+    // Foundations only take one card at a time
+    RunOnFoundation
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    InvalidMove { reason: InvalidReason },
+    MovePlayed { mv: Move },
+    // The talon was turned back over into the empty stock
+    StockRecycled { cards: usize },
+    // A face-down card on a column was turned over
+    CardRevealed { value: u8 },
+    // A foundation holds all 13 cards of its suit
+    FoundationComplete { suit: Suit },
+    Won
+}
@@ -9,9 +9,28 @@ use rodio::OutputStream;
 #[cfg(target_arch="wasm32")]
 use wasm_bindgen::prelude::*;
 
+pub mod animation;
+pub mod audio;
+#[cfg(feature = "cheats")]
+pub mod cheats;
+pub mod controls;
+pub mod demo;
+pub mod events;
+pub mod moves;
+pub mod notation;
+pub mod pile;
+#[cfg(feature = "profiling")]
+pub mod profiling;
 mod render;
-mod systems;
+pub mod rules;
+pub mod save;
+pub mod scene;
+pub mod settings;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod systems;
 
+use audio::Audio;
 use systems::GameState;
 
 use crate::systems::SCREEN_SIZE;
@@ -59,12 +78,23 @@ pub fn run() {
     let (stream, stream_handle) = OutputStream::try_default().unwrap();
     std::mem::forget(stream);
 
-    let mut game_state = GameState::new(stream_handle);
+    let audio = match Audio::new(stream_handle) {
+        Ok(audio) => audio,
+        Err(e) => {
+            log::error!("{:?}", e);
+            Audio::silent()
+        }
+    };
+
+    let mut game_state = GameState::new(audio);
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::RedrawRequested(window_id) if window_id == render_state.window().id() => {
             game_state.update();
+            game_state.step_demo();
             render_state.update(&game_state);
+            // Nothing outside the game listens for events yet
+            game_state.drain_events();
             match render_state.render() {
                 Ok(_) => {},
                 Err(wgpu::SurfaceError::Lost) => render_state.resize(render_state.size()),
@@ -75,21 +105,18 @@ pub fn run() {
         Event::MainEventsCleared => {
             render_state.window().request_redraw();
         },
+        // The loop never returns, so the game isn't dropped on every platform
+        Event::LoopDestroyed => {
+            game_state.flush_autosave();
+            game_state.shutdown_audio();
+        },
         Event::WindowEvent {
             window_id,
             ref event,
-        } if window_id == render_state.window().id() => if !game_state.input(event) {
+        } if window_id == render_state.window().id() && !game_state.input(event) => {
             match event {
-                WindowEvent::CloseRequested
-                | WindowEvent::KeyboardInput {
-                    input:
-                        KeyboardInput {
-                            state: ElementState::Pressed,
-                            virtual_keycode: Some(VirtualKeyCode::Escape),
-                            ..
-                        },
-                    ..
-                } => *control_flow = ControlFlow::Exit,
+                // Escape cancels a pick up like Space does, so only closing the window quits
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                 WindowEvent::Resized(physical_size) => {
                     render_state.resize(*physical_size);
                 },
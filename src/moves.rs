@@ -0,0 +1,1034 @@
+use std::fmt;
+use instant::Duration;
+
+use crate::events::GameEvent;
+use crate::pile::Pile;
+use crate::rules::{GameVariant, UndoPolicy};
+use crate::systems::{GameState, Card, PileRef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    FlipStock,
+    RecycleTalon,
+    TalonToTableau { to: usize },
+    TalonToFoundation { to: usize },
+    TableauToTableau { from: usize, start: usize, to: usize },
+    // Always the top card alone, a run can't be written as a foundation move
+    TableauToFoundation { from: usize, to: usize },
+    FoundationToTableau { from: usize, to: usize }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveRecord {
+    pub mv: Move,
+    // Time since the game started
+    pub at: Duration,
+    // What undo needs that the move alone doesn't say: how many cards moved,
+    // whether a card was turned over behind them, the fanned talon count before
+    // the move and the points it scored
+    pub cards: usize,
+    pub revealed: bool,
+    pub talon_shown: usize,
+    pub score: i32,
+    // Bottom card of those that moved, none for a recycle
+    pub card: Option<u8>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    HandNotEmpty,
+    Illegal(Move),
+    RunToFoundation,
+    NothingToUndo,
+    UndoForbidden,
+    BadNotation
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::HandNotEmpty => write!(f, "cannot apply a move while cards are held"),
+            MoveError::Illegal(mv) => write!(f, "{:?} is not legal in this position", mv),
+            MoveError::RunToFoundation => write!(f, "only one card at a time can go on a foundation"),
+            MoveError::NothingToUndo => write!(f, "there are no moves to undo"),
+            MoveError::UndoForbidden => write!(f, "undo isn't allowed by the rules"),
+            MoveError::BadNotation => write!(f, "couldn't read the move")
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl Move {
+    pub fn apply(&self, game: &mut GameState) -> Result<(), MoveError> {
+        game.apply_move(self)
+    }
+
+    // Only the last move played can be taken back
+    pub fn undo(&self, game: &mut GameState) -> Result<(), MoveError> {
+        match game.history.last() {
+            Some(record) if record.mv == *self => game.undo(),
+            Some(_) => Err(MoveError::Illegal(*self)),
+            None => Err(MoveError::NothingToUndo)
+        }
+    }
+}
+
+impl GameState {
+    // Always in the same order, so the demo and anything replaying a seed stays reproducible,
+    // and 'find_hint' and 'hints' depend on it too:
+    //   1. onto the foundations, by foundation, the talon before the columns left to right
+    //   2. column to column, by source then destination, longest run first
+    //   3. talon to column, left to right
+    //   4. foundation to column, by foundation then column
+    //   5. the stock, a draw or a recycle
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+
+        for (to, foundation) in self.foundations.iter().enumerate() {
+            if self.talon.cards.first().is_some_and(|card| self.can_place_on_foundation(foundation, card)) {
+                moves.push(Move::TalonToFoundation { to });
+            }
+            for (from, source) in self.tableaux.iter().enumerate() {
+                if source.cards.last().is_some_and(|card| self.can_place_on_foundation(foundation, card)) {
+                    moves.push(Move::TableauToFoundation { from, to });
+                }
+            }
+        }
+
+        let max_movable = self.max_movable();
+        for (from, source) in self.tableaux.iter().enumerate() {
+            let first_shown = source.cards.len() - source.shown_cards as usize;
+            for (to, tableau) in self.tableaux.iter().enumerate() {
+                if to == from {
+                    continue;
+                }
+                for start in first_shown..source.cards.len() {
+                    // A whole column moved to an empty one leaves the board as it was
+                    if start == 0 && tableau.cards.is_empty() {
+                        continue;
+                    }
+                    let run = &source.cards[start..];
+                    if run.len() <= max_movable && self.is_valid_run(run) && self.tableau_accepts(tableau, &run[0]) {
+                        moves.push(Move::TableauToTableau { from, start, to });
+                    }
+                }
+            }
+        }
+
+        if let Some(card) = self.talon.cards.first() {
+            for (to, tableau) in self.tableaux.iter().enumerate() {
+                if self.tableau_accepts(tableau, card) {
+                    moves.push(Move::TalonToTableau { to });
+                }
+            }
+        }
+
+        for (from, foundation) in self.foundations.iter().enumerate() {
+            if let Some(card) = foundation.cards.first() {
+                for (to, tableau) in self.tableaux.iter().enumerate() {
+                    if self.tableau_accepts(tableau, card) {
+                        moves.push(Move::FoundationToTableau { from, to });
+                    }
+                }
+            }
+        }
+
+        if !self.stock.cards.is_empty() {
+            moves.push(Move::FlipStock);
+        } else if self.can_recycle() {
+            moves.push(Move::RecycleTalon);
+        }
+
+        moves
+    }
+
+    pub fn has_any_move(&self) -> bool {
+        !self.legal_moves().is_empty()
+    }
+
+    // Cheap game over check, nothing left to draw and nothing on the board can move.
+    // Hidden cards turn over as soon as they're uncovered, so there's no flip to wait for
+    pub fn is_obviously_stuck(&self) -> bool {
+        if self.is_won() || self.is_trivially_winnable() {
+            return false;
+        }
+        // A talon that can't go back to the stock is as spent as an empty one
+        self.stock.cards.is_empty() && !self.can_recycle() && !self.has_any_move()
+    }
+
+    // Rough 0-100 rating of how hard the position looks, a heuristic estimate and not a
+    // solver's verdict. Weighs how much of the tableaux is face down, how many Aces and 2s
+    // are buried under other cards and how few moves the board offers
+    pub fn deal_difficulty(&self) -> u32 {
+        // Most cards a Klondike deal can have face down
+        const MAX_HIDDEN: f32 = 21.0;
+
+        let hidden = (self.hidden_card_count() as f32 / MAX_HIDDEN).min(1.0);
+
+        let buried_low_cards = self.tableaux.iter()
+            .flat_map(|tableau| {
+                let first_shown = tableau.cards.len() - tableau.shown_cards as usize;
+                tableau.cards[..first_shown].iter()
+            })
+            .filter(|card| card.rank <= 1)
+            .count();
+        let buried = buried_low_cards as f32 / 8.0;
+
+        let board_moves = self.legal_moves().iter()
+            .filter(|mv| !matches!(mv, Move::FlipStock | Move::RecycleTalon))
+            .count();
+        let stuck = 1.0 - (board_moves as f32 / 6.0).min(1.0);
+
+        (hidden * 40.0 + buried * 30.0 + stuck * 30.0).round() as u32
+    }
+
+    // 'deal_difficulty' for a game under way, also 0-100 and just as much a heuristic. Counts
+    // the cards still to go up, those face down, those covering a card its foundation needs
+    // soon, and whether the board has anything to play. Goes through every legal move, so
+    // it's for an occasional trend rather than every frame
+    pub fn remaining_difficulty(&self) -> u32 {
+        if self.is_won() {
+            return 0;
+        }
+
+        let left = 1.0 - self.foundation_progress() as f32 / self.card_count() as f32;
+        let hidden = (self.hidden_card_count() as f32 / 21.0).min(1.0);
+
+        // Cards of each suit already up, in the order of a card's value / 13
+        let mut built = [0usize; 4];
+        for foundation in self.foundations.iter() {
+            if let Some(top) = foundation.cards.first() {
+                let suit = (top.value / 13) as usize;
+                built[suit] = built[suit].max(foundation.cards.len());
+            }
+        }
+        let ascending = self.rules.foundation_ascending;
+        let blocked_low_cards = self.tableaux.iter()
+            .flat_map(|tableau| tableau.cards[..tableau.cards.len().saturating_sub(1)].iter())
+            .filter(|card| {
+                let from_start = if ascending { card.rank as usize } else { 12 - card.rank as usize };
+                from_start <= built[(card.value / 13) as usize] + 1
+            })
+            .count();
+        let blocked = (blocked_low_cards as f32 / 8.0).min(1.0);
+
+        let stuck = if !self.has_any_move() {
+            1.0
+        } else if self.available_foundation_plays() > 0 {
+            0.0
+        } else {
+            0.5
+        };
+
+        (left * 35.0 + hidden * 25.0 + blocked * 25.0 + stuck * 15.0).round() as u32
+    }
+
+    // Cards that can never reach a foundation, a conservative heuristic rather than a solver:
+    // it misses most dead cards but anything it returns is dead. A card only leaves its column
+    // at the head of a run, itself or one starting beneath it. When no empty column would take
+    // the head of any such run, and every card the head could go on lies under it, nothing from
+    // that card down can ever move. If it also has to wait for an earlier card of its suit lying
+    // beneath it, it and everything under it are dead. Free cells could park anything
+    pub fn dead_cards(&self) -> Vec<u8> {
+        if let GameVariant::FreeCell { .. } = self.rules.variant {
+            return vec![];
+        }
+        let mut dead = vec![];
+        for tableau in &self.tableaux {
+            let cards = &tableau.cards;
+            let pinned = |i: usize| {
+                let head = &cards[i];
+                let below = &cards[..i];
+                // Each value turns up once per deck
+                let parents = (0..52).filter(|value| self.can_place_on_tableau(&Card::new(*value), head)).count() * self.card_count() / 52;
+                let parents_below = below.iter().filter(|other| self.can_place_on_tableau(other, head)).count();
+                !self.empty_column_accepts(head) && parents_below == parents
+            };
+            let stuck_at = (0..cards.len()).rev().find(|j| {
+                let card = &cards[*j];
+                let waits_on_below = cards[..*j].iter().any(|other| other.suit == card.suit && self.goes_up_before(other, card));
+                waits_on_below && (0..=*j).rev()
+                    .take_while(|i| self.is_valid_run(&cards[*i..=*j]))
+                    .all(pinned)
+            });
+            if let Some(j) = stuck_at {
+                dead.extend(cards[..=j].iter().map(|card| card.value));
+            }
+        }
+        dead
+    }
+
+    fn goes_up_before(&self, first: &Card, second: &Card) -> bool {
+        if self.rules.foundation_ascending { first.rank < second.rank } else { first.rank > second.rank }
+    }
+
+    // Prefers a move on the board, only suggesting the stock when nothing else is possible
+    pub fn find_hint(&self) -> Option<Move> {
+        let moves = self.legal_moves();
+        moves.iter()
+            .find(|mv| !matches!(mv, Move::FlipStock | Move::RecycleTalon))
+            .or(moves.first())
+            .copied()
+    }
+
+    // Every legal move, most useful first: plays up, moves that turn a card over,
+    // the rest of the board and lastly the stock
+    pub fn hints(&self) -> Vec<Move> {
+        let mut moves = self.legal_moves();
+        moves.sort_by_key(|mv| match mv {
+            Move::TalonToFoundation { .. } | Move::TableauToFoundation { .. } => 0,
+            _ if self.reveal_preview(mv).is_some() => 1,
+            Move::FlipStock | Move::RecycleTalon => 3,
+            _ => 2
+        });
+        moves
+    }
+
+    // Each call gives the next of 'hints', wrapping around, and starts over once the board changes
+    pub fn next_hint(&mut self) -> Option<Move> {
+        let hints = self.hints();
+        if hints.is_empty() { return None; }
+
+        let key = self.position_key();
+        let index = match self.hint_cycle {
+            Some((cycle_key, index)) if cycle_key == key => (index + 1) % hints.len(),
+            _ => 0
+        };
+        self.hint_cycle = Some((key, index));
+        Some(hints[index])
+    }
+
+    pub fn target_foundation(&self, card: &Card) -> Option<usize> {
+        self.foundations.iter().position(|foundation| self.can_place_on_foundation(foundation, card))
+    }
+
+    // Columns the talon top could be played to, empty ones by the same rule as any drop
+    pub fn talon_tableau_moves(&self) -> Vec<usize> {
+        let Some(card) = self.talon.cards.first() else { return vec![] };
+        (0..self.tableaux.len())
+            .filter(|t| self.tableau_accepts(&self.tableaux[*t], card))
+            .collect()
+    }
+
+    // Cards that could go up right now, each counted once however many foundations take it
+    pub fn available_foundation_plays(&self) -> usize {
+        self.talon.cards.first().into_iter()
+            .chain(self.tableaux.iter().filter_map(|tableau| tableau.cards.last()))
+            .filter(|card| self.target_foundation(card).is_some())
+            .count()
+    }
+
+    // Piles the held cards could be put on, not counting where they were picked up from
+    pub fn legal_destinations(&self) -> Vec<PileRef> {
+        self.destinations(&self.hand.cards, self.hand_origin)
+    }
+
+    // Whether 'legal_destinations' has this pile, without building the rest of the list
+    pub fn can_drop_on(&self, pile: PileRef) -> bool {
+        let run = &self.hand.cards;
+        let source = self.hand_origin;
+        if run.is_empty() || pile == source {
+            return false;
+        }
+        match pile {
+            PileRef::Foundation(f) => {
+                run.len() == 1 && !matches!(source, PileRef::Foundation(_))
+                    && self.foundations.get(f).is_some_and(|foundation| self.can_place_on_foundation(foundation, &run[0]))
+            },
+            PileRef::Tableau(t) => {
+                run.len() <= self.max_movable()
+                    && self.tableaux.get(t).is_some_and(|tableau| self.tableau_accepts(tableau, &run[0]))
+            },
+            PileRef::Stock | PileRef::Talon => false
+        }
+    }
+
+    fn destinations(&self, run: &[Card], source: PileRef) -> Vec<PileRef> {
+        let mut destinations = vec![];
+        if run.is_empty() {
+            return destinations;
+        }
+
+        if run.len() == 1 && !matches!(source, PileRef::Foundation(_)) {
+            for (f, foundation) in self.foundations.iter().enumerate() {
+                if self.can_place_on_foundation(foundation, &run[0]) {
+                    destinations.push(PileRef::Foundation(f));
+                }
+            }
+        }
+        if run.len() > self.max_movable() {
+            return destinations;
+        }
+        for (t, tableau) in self.tableaux.iter().enumerate() {
+            if source != PileRef::Tableau(t) && self.tableau_accepts(tableau, &run[0]) {
+                destinations.push(PileRef::Tableau(t));
+            }
+        }
+        destinations
+    }
+
+    // Everywhere the cards from 'card_index' up could go if they were picked up,
+    // for highlighting before anything is held
+    pub fn destinations_for(&self, source: PileRef, card_index: usize) -> Vec<PileRef> {
+        match self.run_from(source, card_index) {
+            Some(run) => self.destinations(run, source),
+            None => vec![]
+        }
+    }
+
+    // The cards picked up by taking 'card_index', the talon and foundations only give up
+    // their top card at index 0. Face-up cards that don't form a run can't be taken together
+    fn run_from(&self, pile: PileRef, card_index: usize) -> Option<&[Card]> {
+        match pile {
+            PileRef::Stock => None,
+            PileRef::Talon => {
+                if card_index != 0 { return None; }
+                Some(&self.talon.cards[..self.talon.cards.len().min(1)])
+            },
+            PileRef::Foundation(f) => {
+                if card_index != 0 { return None; }
+                Some(&self.foundations[f].cards[..self.foundations[f].cards.len().min(1)])
+            },
+            PileRef::Tableau(t) => {
+                let tableau = &self.tableaux[t];
+                let first_shown = tableau.cards.len() - tableau.shown_cards as usize;
+                if card_index < first_shown || card_index >= tableau.cards.len() { return None; }
+                let run = &tableau.cards[card_index..];
+                if !self.is_valid_run(run) { return None; }
+                Some(run)
+            }
+        }
+    }
+
+    // Where a double click would send the card, a foundation if possible and otherwise
+    // a tableau, preferring one with cards so empty columns aren't used up
+    pub fn suggested_move_for(&self, pile: PileRef, card_index: usize) -> Option<Move> {
+        let run = self.run_from(pile, card_index)?;
+
+        let destinations = self.destinations(run, pile);
+        let destination = destinations.iter()
+            .find(|destination| matches!(destination, PileRef::Foundation(_)))
+            .or(destinations.iter().find(|destination| match destination {
+                PileRef::Tableau(t) => !self.tableaux[*t].cards.is_empty(),
+                _ => false
+            }))
+            .or(destinations.first())?;
+
+        match (pile, *destination) {
+            (PileRef::Talon, PileRef::Foundation(to)) => Some(Move::TalonToFoundation { to }),
+            (PileRef::Talon, PileRef::Tableau(to)) => Some(Move::TalonToTableau { to }),
+            (PileRef::Foundation(from), PileRef::Tableau(to)) => Some(Move::FoundationToTableau { from, to }),
+            (PileRef::Tableau(from), PileRef::Foundation(to)) => Some(Move::TableauToFoundation { from, to }),
+            (PileRef::Tableau(from), PileRef::Tableau(to)) => Some(Move::TableauToTableau { from, start: card_index, to }),
+            _ => None
+        }
+    }
+
+    // Nothing left to uncover or draw, so every remaining card can go straight up
+    pub fn is_trivially_winnable(&self) -> bool {
+        self.stock.cards.is_empty() && self.talon.cards.is_empty() && self.hidden_card_count() == 0
+    }
+
+    // Each card left takes exactly one move up once nothing is hidden or undrawn
+    pub fn moves_to_autowin(&self) -> Option<usize> {
+        if !self.is_trivially_winnable() { return None; }
+        Some(self.card_count() - self.foundation_progress())
+    }
+
+    // Lowest ranked card that can go up, so Aces and 2s are never left underneath others
+    fn next_autocomplete_move(&self) -> Option<Move> {
+        let mut best: Option<(u8, Move)> = None;
+        let mut consider = |rank: u8, mv: Move| {
+            match best {
+                Some((best_rank, _)) if best_rank <= rank => {},
+                _ => best = Some((rank, mv))
+            }
+        };
+
+        if let Some(card) = self.talon.cards.first() {
+            if let Some(to) = self.target_foundation(card) {
+                consider(card.rank, Move::TalonToFoundation { to });
+            }
+        }
+        for (from, tableau) in self.tableaux.iter().enumerate() {
+            if let Some(card) = tableau.cards.last() {
+                if let Some(to) = self.target_foundation(card) {
+                    consider(card.rank, Move::TableauToFoundation { from, to });
+                }
+            }
+        }
+
+        best.map(|(_, mv)| mv)
+    }
+
+    // Plays one card to the foundations, returns false once nothing more can go up
+    pub fn autocomplete_step(&mut self) -> bool {
+        match self.next_autocomplete_move() {
+            Some(mv) => self.apply_move(&mv).is_ok(),
+            None => false
+        }
+    }
+
+    pub fn apply_move(&mut self, mv: &Move) -> Result<(), MoveError> {
+        if !self.hand.cards.is_empty() {
+            return Err(MoveError::HandNotEmpty);
+        }
+        if !self.legal_moves().contains(mv) {
+            return Err(MoveError::Illegal(*mv));
+        }
+
+        self.talon_shown_before = self.talon_shown;
+        let cards = match *mv {
+            Move::FlipStock => self.rules.draw_count.min(self.stock.cards.len()),
+            Move::RecycleTalon => self.talon.cards.len(),
+            Move::TableauToTableau { from, start, .. } => self.tableaux[from].cards.len() - start,
+            _ => 1
+        };
+
+        match *mv {
+            Move::FlipStock => {
+                self.flip_stock();
+            },
+            Move::RecycleTalon => {
+                self.recycle_talon();
+            },
+            Move::TalonToTableau { to } => {
+                let card = self.take_talon_top();
+                self.push_to_tableau(to, vec![card]);
+            },
+            Move::TalonToFoundation { to } => {
+                let card = self.take_talon_top();
+                self.foundations[to].push(vec![card]);
+            },
+            Move::TableauToTableau { from, start, to } => {
+                let run = self.take_from_tableau(from, start);
+                self.push_to_tableau(to, run);
+            },
+            Move::TableauToFoundation { from, to } => {
+                let start = self.tableaux[from].cards.len() - 1;
+                let run = self.take_from_tableau(from, start);
+                self.foundations[to].push(run);
+            },
+            Move::FoundationToTableau { from, to } => {
+                let run = self.foundations[from].pop_run(1);
+                self.push_to_tableau(to, run);
+            }
+        }
+
+        self.record_move(*mv, cards);
+        if let Move::TalonToFoundation { to } | Move::TableauToFoundation { to, .. } = *mv {
+            self.check_foundation_complete(to);
+        }
+        self.check_win();
+        Ok(())
+    }
+
+    // Called once the cards have left their pile, so turning over the card they
+    // uncovered is part of the move
+    pub(crate) fn record_move(&mut self, mv: Move, cards: usize) {
+        let revealed = match GameState::source_tableau(&mv) {
+            Some(from) => self.reveal_top(from),
+            None => false
+        };
+
+        let before = self.score;
+        self.score = (self.score + self.move_points(&mv, revealed)).max(0);
+        let card = self.moved_card(&mv, cards);
+        self.history.push(MoveRecord {
+            mv,
+            at: self.game_time,
+            cards,
+            revealed,
+            talon_shown: self.talon_shown_before,
+            score: self.score - before,
+            card
+        });
+        self.emit(GameEvent::MovePlayed { mv });
+        self.autosave_pending = true;
+    }
+
+    // Every move is recorded once its cards have arrived, so the card it moved is read off
+    // where it went: the first of the run on a column, the top of a foundation or the talon
+    fn moved_card(&self, mv: &Move, cards: usize) -> Option<u8> {
+        let card = match *mv {
+            Move::FlipStock => self.talon.cards.first(),
+            Move::RecycleTalon => None,
+            Move::TalonToTableau { to } | Move::TableauToTableau { to, .. } | Move::FoundationToTableau { to, .. } => {
+                let column = &self.tableaux[to].cards;
+                column.get(column.len().checked_sub(cards)?)
+            },
+            Move::TalonToFoundation { to } | Move::TableauToFoundation { to, .. } => self.foundations[to].cards.first()
+        };
+        card.map(|card| card.value)
+    }
+
+    // Standard Klondike scoring
+    fn move_points(&self, mv: &Move, revealed: bool) -> i32 {
+        let points = match mv {
+            Move::TalonToTableau { .. } => 5,
+            Move::TalonToFoundation { .. } | Move::TableauToFoundation { .. } => 10,
+            Move::FoundationToTableau { .. } => -15,
+            Move::RecycleTalon if self.rules.draw_count == 1 => -100,
+            _ => 0
+        };
+        if revealed { points + 5 } else { points }
+    }
+
+    // The face-down card a move would turn over, without turning it
+    pub fn reveal_preview(&self, mv: &Move) -> Option<u8> {
+        let (from, start) = match *mv {
+            Move::TableauToTableau { from, start, .. } => (from, start),
+            Move::TableauToFoundation { from, .. } => (from, self.tableaux[from].cards.len().checked_sub(1)?),
+            _ => return None
+        };
+        let (hidden, _) = self.tableau_split(from);
+        if start > 0 && start == hidden.len() {
+            Some(hidden[start - 1].value)
+        } else {
+            None
+        }
+    }
+
+    fn source_tableau(mv: &Move) -> Option<usize> {
+        match *mv {
+            Move::TableauToTableau { from, .. } | Move::TableauToFoundation { from, .. } => Some(from),
+            _ => None
+        }
+    }
+
+    // Puts the last move back exactly as it was, including the talon fan and the score
+    pub fn undo(&mut self) -> Result<(), MoveError> {
+        if self.rules.undo_policy == UndoPolicy::Forbidden {
+            return Err(MoveError::UndoForbidden);
+        }
+        if !self.hand.cards.is_empty() {
+            return Err(MoveError::HandNotEmpty);
+        }
+        let record = self.history.pop().ok_or(MoveError::NothingToUndo)?;
+
+        if record.revealed {
+            if let Some(from) = GameState::source_tableau(&record.mv) {
+                self.tableaux[from].shown_cards -= 1;
+            }
+        }
+
+        match record.mv {
+            Move::FlipStock => {
+                for _ in 0..record.cards {
+                    let card = self.talon.cards.remove(0);
+                    self.stock.cards.push(card);
+                }
+            },
+            Move::RecycleTalon => {
+                self.talon.cards = self.stock.cards.drain(..).collect();
+                if let Some(passes) = &mut self.stock_passes_remaining {
+                    *passes += 1;
+                }
+            },
+            Move::TalonToTableau { to } => {
+                let run = self.tableaux[to].pop_run(1);
+                self.talon.push(run);
+            },
+            Move::TalonToFoundation { to } => {
+                let run = self.foundations[to].pop_run(1);
+                self.talon.push(run);
+            },
+            Move::TableauToTableau { from, to, .. } => {
+                let run = self.tableaux[to].pop_run(record.cards);
+                self.tableaux[from].push(run);
+            },
+            Move::TableauToFoundation { from, to } => {
+                let run = self.foundations[to].pop_run(1);
+                self.tableaux[from].push(run);
+            },
+            Move::FoundationToTableau { from, to } => {
+                let run = self.tableaux[to].pop_run(1);
+                self.foundations[from].push(run);
+            }
+        }
+
+        self.talon_shown = record.talon_shown;
+        self.score -= record.score;
+        if let UndoPolicy::ScorePenalty(penalty) = self.rules.undo_policy {
+            self.score = (self.score - penalty).max(0);
+        }
+        self.autosave_pending = true;
+        // Winning again after undoing the last card is a new win
+        if !self.is_won() {
+            self.winning_move = None;
+            self.cascade = None;
+        }
+        Ok(())
+    }
+
+    pub fn move_timeline(&self) -> Vec<(Duration, Move)> {
+        self.history.iter().map(|record| (record.at, record.mv)).collect()
+    }
+
+    // The move putting the held cards on 'to' would be, moving an Ace between
+    // foundations or dropping cards back where they came from isn't a move.
+    // Held runs are refused by foundations outright, there's no move they could be
+    pub(crate) fn hand_move(&self, to: PileRef) -> Result<Option<Move>, MoveError> {
+        if self.hand.cards.len() > 1 && matches!(to, PileRef::Foundation(_)) {
+            return Err(MoveError::RunToFoundation);
+        }
+        let mv = match (self.hand_origin, to) {
+            (PileRef::Talon, PileRef::Tableau(to)) => Some(Move::TalonToTableau { to }),
+            (PileRef::Talon, PileRef::Foundation(to)) => Some(Move::TalonToFoundation { to }),
+            (PileRef::Foundation(from), PileRef::Tableau(to)) => Some(Move::FoundationToTableau { from, to }),
+            (PileRef::Tableau(from), PileRef::Tableau(to)) => {
+                if from == to { return Ok(None); }
+                Some(Move::TableauToTableau { from, start: self.tableaux[from].cards.len(), to })
+            },
+            (PileRef::Tableau(from), PileRef::Foundation(to)) => Some(Move::TableauToFoundation { from, to }),
+            _ => None
+        };
+        Ok(mv)
+    }
+
+    fn take_from_tableau(&mut self, from: usize, start: usize) -> Vec<Card> {
+        let tableau = &mut self.tableaux[from];
+        tableau.pop_run(tableau.cards.len() - start)
+    }
+
+    fn push_to_tableau(&mut self, to: usize, run: Vec<Card>) {
+        self.tableaux[to].push(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::Audio;
+    use crate::rules::Rules;
+
+    fn game(seed: u64) -> GameState {
+        GameState::new_with_seed(Audio::silent(), Rules::default(), seed)
+    }
+
+    #[test]
+    fn legal_moves_on_a_known_deal() {
+        // The A♦ topping the last column can start any of the four foundations or go on
+        // the 2♠, the 3♣ fits on the 4♦, the 8♣ on the 9♥ and the stock can be drawn from
+        let game = game(0);
+        assert_eq!(game.legal_moves().len(), 8);
+    }
+
+    #[test]
+    fn no_move_shifts_a_whole_column_onto_an_empty_one() {
+        let mut game = game(0);
+        // A lone King, which any empty column takes
+        game.tableaux[0].cards = vec![Card::new(12)];
+        game.tableaux[6].cards.clear();
+        game.tableaux[6].shown_cards = 0;
+        game.tableaux[6].calculate_card_quads();
+
+        let moves = game.legal_moves();
+        assert!(!moves.contains(&Move::TableauToTableau { from: 0, start: 0, to: 6 }));
+    }
+
+    // Every card face up in four columns, each running from a King down to an Ace,
+    // and the stock dealt out, so the whole game can go up without another move
+    fn sorted_board() -> GameState {
+        let mut game = game(0);
+        game.stock.cards.clear();
+        for tableau in game.tableaux.iter_mut() {
+            tableau.cards.clear();
+            tableau.shown_cards = 0;
+            tableau.calculate_card_quads();
+        }
+        // Suits in the order of a card's value / 13, spades and hearts then clubs and diamonds
+        for (t, (first, second)) in [(0, 1), (1, 0), (2, 3), (3, 2)].into_iter().enumerate() {
+            let run = (0..13u8)
+                .map(|i| Card::new(if i % 2 == 0 { first } else { second } * 13 + 12 - i))
+                .collect();
+            game.tableaux[t].push(run);
+        }
+        game
+    }
+
+    #[test]
+    fn autocomplete_plays_every_card_left_one_step_at_a_time() {
+        let mut game = sorted_board();
+        for t in 0..4 {
+            let ace = game.tableaux[t].pop_run(1);
+            game.foundations[t].push(ace);
+        }
+        assert!(game.is_trivially_winnable());
+        assert_eq!(game.moves_to_autowin(), Some(48));
+
+        let mut steps = 0;
+        while game.autocomplete_step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 48);
+        assert!(game.is_won());
+    }
+
+    // Nothing in the stock, a lone King on the first column and the rest topped by cards
+    // with nowhere to go: the four 2s with no Aces up, a 4♠ and a 6♠
+    fn stuck_board() -> GameState {
+        let mut game = game(0);
+        game.stock.cards.clear();
+        let (king, tops) = (12, [1, 14, 27, 40, 3, 5]);
+        let mut hidden: Vec<Card> = (0..52)
+            .filter(|value| *value != king && !tops.contains(value))
+            .map(Card::new)
+            .collect();
+        game.tableaux[0].cards = vec![Card::new(king)];
+        for (i, top) in tops.iter().enumerate() {
+            let mut cards: Vec<Card> = hidden.drain(..hidden.len() / (tops.len() - i)).collect();
+            cards.push(Card::new(*top));
+            game.tableaux[i + 1].cards = cards;
+        }
+        for tableau in game.tableaux.iter_mut() {
+            tableau.shown_cards = tableau.cards.len().min(1) as u8;
+            tableau.calculate_card_quads();
+        }
+        game
+    }
+
+    #[test]
+    fn a_board_with_nothing_to_play_is_stuck() {
+        let game = stuck_board();
+        assert!(game.legal_moves().is_empty());
+        assert!(game.is_obviously_stuck());
+    }
+
+    #[test]
+    fn a_board_with_a_card_left_to_draw_isnt_stuck() {
+        let mut game = stuck_board();
+        let card = game.tableaux[6].pop_run(1);
+        game.stock.cards = card;
+        game.ensure_top_revealed();
+        assert!(!game.is_obviously_stuck());
+    }
+
+    #[test]
+    fn a_board_that_only_has_to_go_up_isnt_stuck() {
+        assert!(!sorted_board().is_obviously_stuck());
+    }
+
+    fn thoughtful(seed: u64) -> GameState {
+        let rules = Rules { variant: GameVariant::Thoughtful, ..Rules::default() };
+        GameState::new_with_seed(Audio::silent(), rules, seed)
+    }
+
+    #[test]
+    fn face_up_cards_that_arent_a_run_dont_move_together() {
+        // The A♥ of the fourth column fits on the 2♠ topping the second,
+        // but the 9♥ lying on it would have to go along
+        let mut game = thoughtful(0);
+        assert!(!game.is_valid_run(&game.tableaux[3].cards[2..]));
+        let mv = Move::TableauToTableau { from: 3, start: 2, to: 1 };
+        assert!(!game.legal_moves().contains(&mv));
+        assert_eq!(game.apply_move(&mv), Err(MoveError::Illegal(mv)));
+        assert!(game.destinations_for(PileRef::Tableau(3), 2).is_empty());
+    }
+
+    #[test]
+    fn every_column_move_offered_is_a_run() {
+        for seed in 0..20 {
+            let game = thoughtful(seed);
+            for mv in game.legal_moves() {
+                if let Move::TableauToTableau { from, start, .. } = mv {
+                    assert!(game.is_valid_run(&game.tableaux[from].cards[start..]), "seed {} offered {:?}", seed, mv);
+                }
+            }
+        }
+    }
+
+    // The A♦ up from the last column of seed 0, turning over the 5♥ under it
+    fn play_ace_with(undo_policy: UndoPolicy) -> GameState {
+        let rules = Rules { undo_policy, ..Rules::default() };
+        let mut game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        game.score = 50;
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        assert_eq!(game.score(), 65);
+        game
+    }
+
+    #[test]
+    fn free_undo_puts_the_move_and_its_points_back() {
+        let mut game = play_ace_with(UndoPolicy::Free);
+        assert_eq!(game.undo(), Ok(()));
+        assert_eq!(game.score(), 50);
+        assert_eq!(game.tableaux[6].cards.len(), 7);
+        assert_eq!(game.tableaux[6].shown_cards, 1);
+        assert_eq!(game.foundations[0].cards.len(), 0);
+    }
+
+    #[test]
+    fn penalised_undo_also_takes_points_off() {
+        let mut game = play_ace_with(UndoPolicy::ScorePenalty(20));
+        assert_eq!(game.undo(), Ok(()));
+        assert_eq!(game.score(), 30);
+        assert_eq!(game.tableaux[6].cards.len(), 7);
+    }
+
+    #[test]
+    fn forbidden_undo_leaves_the_move_in_place() {
+        let mut game = play_ace_with(UndoPolicy::Forbidden);
+        assert_eq!(game.undo(), Err(MoveError::UndoForbidden));
+        assert_eq!(game.score(), 65);
+        assert_eq!(game.foundations[0].cards.len(), 1);
+    }
+
+    #[test]
+    fn deal_difficulty_is_the_same_for_the_same_seed() {
+        for seed in 0..10 {
+            assert_eq!(game(seed).deal_difficulty(), game(seed).deal_difficulty());
+            assert!(game(seed).deal_difficulty() <= 100);
+        }
+    }
+
+    #[test]
+    fn a_face_up_deal_rates_easier_than_a_standard_one() {
+        for seed in 0..10 {
+            assert!(thoughtful(seed).deal_difficulty() < game(seed).deal_difficulty(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn a_king_alone_beside_an_empty_column_is_still_stuck() {
+        let mut game = stuck_board();
+        // Bury the last column under the one before it, leaving it empty
+        let cards = std::mem::take(&mut game.tableaux[6].cards);
+        game.tableaux[5].cards.splice(0..0, cards);
+        game.tableaux[6].shown_cards = 0;
+        game.tableaux[5].calculate_card_quads();
+        game.tableaux[6].calculate_card_quads();
+        assert!(game.legal_moves().is_empty());
+        assert!(game.is_obviously_stuck());
+    }
+
+    // Seed 0 with its first column emptied and the given card alone on the talon
+    fn talon_beside_empty_column(value: u8) -> GameState {
+        let mut game = game(0);
+        game.tableaux[0].cards.clear();
+        game.tableaux[0].shown_cards = 0;
+        game.tableaux[0].calculate_card_quads();
+        game.talon.cards = vec![Card::new(value)];
+        game
+    }
+
+    #[test]
+    fn an_empty_talon_has_no_column_moves() {
+        let mut game = game(0);
+        game.talon.cards.clear();
+        assert!(game.talon_tableau_moves().is_empty());
+    }
+
+    #[test]
+    fn only_a_king_from_the_talon_goes_on_an_empty_column() {
+        // K♥, then the Q♠ which nothing on seed 0 takes either
+        assert_eq!(talon_beside_empty_column(25).talon_tableau_moves(), vec![0]);
+        assert!(talon_beside_empty_column(11).talon_tableau_moves().is_empty());
+    }
+
+    #[test]
+    fn the_board_gets_easier_as_cards_go_up() {
+        let mut game = sorted_board();
+        let mut difficulty = game.remaining_difficulty();
+        let start = difficulty;
+        while game.autocomplete_step() {
+            let now = game.remaining_difficulty();
+            assert!(now <= difficulty, "{} after {}", now, difficulty);
+            difficulty = now;
+        }
+        assert!(start > 0);
+        assert_eq!(difficulty, 0);
+    }
+
+    #[test]
+    fn can_drop_on_keeps_the_king_rule_for_empty_columns() {
+        for (value, accepted) in [(25, true), (11, false)] {
+            let mut game = talon_beside_empty_column(value);
+            game.hand.cards = game.talon.pop_run(1);
+            game.hand_origin = PileRef::Talon;
+            assert_eq!(game.can_drop_on(PileRef::Tableau(0)), accepted);
+        }
+    }
+
+    #[test]
+    fn nothing_can_drop_from_an_empty_hand() {
+        let game = talon_beside_empty_column(25);
+        assert!(!game.can_drop_on(PileRef::Tableau(0)));
+    }
+
+    #[test]
+    fn legal_moves_come_in_the_documented_order() {
+        let game = game(0);
+        assert_eq!(game.legal_moves(), vec![
+            Move::TableauToFoundation { from: 6, to: 0 },
+            Move::TableauToFoundation { from: 6, to: 1 },
+            Move::TableauToFoundation { from: 6, to: 2 },
+            Move::TableauToFoundation { from: 6, to: 3 },
+            Move::TableauToTableau { from: 2, start: 2, to: 0 },
+            Move::TableauToTableau { from: 4, start: 4, to: 3 },
+            Move::TableauToTableau { from: 6, start: 6, to: 1 },
+            Move::FlipStock
+        ]);
+    }
+
+    // Seed 0 with the top two cards of the fourth column, the A♥ and 9♥, held together
+    fn holding_run() -> GameState {
+        let mut game = game(0);
+        game.tableaux[3].shown_cards = 2;
+        game.hand.cards = game.tableaux[3].pop_run(2);
+        game.hand_origin = PileRef::Tableau(3);
+        game
+    }
+
+    #[test]
+    fn a_held_run_onto_a_foundation_is_a_run_to_foundation() {
+        let game = holding_run();
+        for f in 0..game.foundations.len() {
+            assert_eq!(game.hand_move(PileRef::Foundation(f)), Err(MoveError::RunToFoundation));
+        }
+        assert_eq!(game.hand_move(PileRef::Tableau(0)), Ok(Some(Move::TableauToTableau { from: 3, start: 2, to: 0 })));
+    }
+
+    #[test]
+    fn a_single_held_card_onto_a_foundation_is_a_move() {
+        let mut game = game(0);
+        game.hand.cards = game.tableaux[6].pop_run(1);
+        game.hand_origin = PileRef::Tableau(6);
+        assert_eq!(game.hand_move(PileRef::Foundation(0)), Ok(Some(Move::TableauToFoundation { from: 6, to: 0 })));
+    }
+
+    #[test]
+    fn a_move_the_position_doesnt_allow_is_illegal() {
+        let mut game = game(0);
+        let mv = Move::TableauToFoundation { from: 0, to: 0 };
+        assert_eq!(game.apply_move(&mv), Err(MoveError::Illegal(mv)));
+    }
+
+    // Seed 0 with the given columns all face up in place of the first ones, the rest emptied
+    fn face_up_columns(columns: &[&[u8]]) -> GameState {
+        let mut game = game(0);
+        for (t, tableau) in game.tableaux.iter_mut().enumerate() {
+            tableau.cards = columns.get(t).map_or(vec![], |values| values.iter().map(|value| Card::new(*value)).collect());
+            tableau.shown_cards = tableau.cards.len() as u8;
+            tableau.calculate_card_quads();
+        }
+        game
+    }
+
+    #[test]
+    fn a_card_that_can_leave_in_a_run_isnt_dead() {
+        // A♠, 7♦, 7♥, 6♠ beside an 8♣: the 6♠ can't go anywhere alone, but with the 7♥ it can
+        let game = face_up_columns(&[&[0, 45, 19, 5], &[33]]);
+        assert!(game.legal_moves().contains(&Move::TableauToTableau { from: 0, start: 2, to: 1 }));
+        assert!(game.dead_cards().is_empty());
+    }
+
+    #[test]
+    fn a_card_pinned_with_every_run_it_heads_is_dead() {
+        // 8♣, 8♠, A♠, 7♥, 7♦, 6♠: the 7♦ could only take the 6♠ onto the 8s beneath them both
+        let column = [33, 7, 0, 19, 45, 5];
+        let game = face_up_columns(&[&column]);
+        assert_eq!(game.dead_cards(), column);
+    }
+}
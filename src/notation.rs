@@ -0,0 +1,143 @@
+use crate::moves::{Move, MoveError, MoveRecord};
+use crate::systems::GameState;
+
+// Foundations are named by the suit on them, in the order of a card's value / 13
+const SUIT_SYMBOLS: [char; 4] = ['♠', '♥', '♣', '♦'];
+const SUIT_LETTERS: [char; 4] = ['s', 'h', 'c', 'd'];
+
+// A pile as it's written, "Stock", "Talon", "T3" or "F♥"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Written {
+    Stock,
+    Talon,
+    Tableau(usize),
+    Foundation(usize)
+}
+
+impl GameState {
+    // Every move played so far, readable like "T3→F♥", "Talon→T5" or "T2→T6(3 cards)"
+    pub fn moves_notation(&self) -> Vec<String> {
+        self.history.iter().map(GameState::notation).collect()
+    }
+
+    fn notation(record: &MoveRecord) -> String {
+        let suit = record.card.map(|value| SUIT_SYMBOLS[(value / 13) as usize]).unwrap_or('?');
+        match record.mv {
+            Move::FlipStock => "Stock→Talon".to_string(),
+            Move::RecycleTalon => "Talon→Stock".to_string(),
+            Move::TalonToTableau { to } => format!("Talon→T{}", to + 1),
+            Move::TalonToFoundation { .. } => format!("Talon→F{}", suit),
+            Move::TableauToTableau { from, to, .. } if record.cards > 1 => {
+                format!("T{}→T{}({} cards)", from + 1, to + 1, record.cards)
+            },
+            Move::TableauToTableau { from, to, .. } => format!("T{}→T{}", from + 1, to + 1),
+            Move::TableauToFoundation { from, .. } => format!("T{}→F{}", from + 1, suit),
+            Move::FoundationToTableau { to, .. } => format!("F{}→T{}", suit, to + 1)
+        }
+    }
+
+    // Plays one move written the way 'moves_notation' writes them, "->" works for the arrow
+    pub fn apply_notation(&mut self, s: &str) -> Result<(), MoveError> {
+        let (from, to) = s.split_once('→').or_else(|| s.split_once("->")).ok_or(MoveError::BadNotation)?;
+        let (to, count) = match to.split_once('(') {
+            Some((to, count)) => {
+                let count = count.trim().strip_suffix(')').ok_or(MoveError::BadNotation)?;
+                let count = count.trim_end_matches("cards").trim_end_matches("card").trim();
+                (to, count.parse::<usize>().map_err(|_| MoveError::BadNotation)?)
+            },
+            None => (to, 1)
+        };
+
+        let mv = match (self.parse_pile(from)?, self.parse_pile(to)?) {
+            (Written::Stock, Written::Talon) => Move::FlipStock,
+            (Written::Talon, Written::Stock) => Move::RecycleTalon,
+            (Written::Talon, Written::Tableau(to)) => Move::TalonToTableau { to },
+            (Written::Talon, Written::Foundation(to)) => Move::TalonToFoundation { to },
+            (Written::Tableau(from), Written::Tableau(to)) => {
+                let start = self.tableaux[from].cards.len().checked_sub(count).ok_or(MoveError::BadNotation)?;
+                Move::TableauToTableau { from, start, to }
+            },
+            (Written::Tableau(from), Written::Foundation(to)) => Move::TableauToFoundation { from, to },
+            (Written::Foundation(from), Written::Tableau(to)) => Move::FoundationToTableau { from, to },
+            _ => return Err(MoveError::BadNotation)
+        };
+        self.apply_move(&mv)
+    }
+
+    // A suit names the foundation already building it, or an empty one if none is
+    fn parse_pile(&self, token: &str) -> Result<Written, MoveError> {
+        let token = token.trim();
+        match token {
+            "Stock" => return Ok(Written::Stock),
+            "Talon" => return Ok(Written::Talon),
+            _ => {}
+        }
+        let mut chars = token.chars();
+        let kind = chars.next().ok_or(MoveError::BadNotation)?;
+        let rest = chars.as_str();
+        match kind {
+            'T' => {
+                let column = rest.parse::<usize>().map_err(|_| MoveError::BadNotation)?;
+                if column == 0 || column > self.tableaux.len() {
+                    return Err(MoveError::BadNotation);
+                }
+                Ok(Written::Tableau(column - 1))
+            },
+            'F' => {
+                let mut suit_chars = rest.chars();
+                let (Some(c), None) = (suit_chars.next(), suit_chars.next()) else {
+                    return Err(MoveError::BadNotation);
+                };
+                let suit = SUIT_SYMBOLS.iter().position(|symbol| *symbol == c)
+                    .or_else(|| SUIT_LETTERS.iter().position(|letter| *letter == c.to_ascii_lowercase()))
+                    .ok_or(MoveError::BadNotation)?;
+                self.foundations.iter()
+                    .position(|foundation| foundation.cards.first().is_some_and(|card| (card.value / 13) as usize == suit))
+                    .or_else(|| self.foundations.iter().position(|foundation| foundation.cards.is_empty()))
+                    .map(Written::Foundation)
+                    .ok_or(MoveError::BadNotation)
+            },
+            _ => Err(MoveError::BadNotation)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::audio::Audio;
+    use crate::rules::Rules;
+
+    fn game(seed: u64) -> GameState {
+        GameState::new_with_seed(Audio::silent(), Rules::default(), seed)
+    }
+
+    #[test]
+    fn a_game_replayed_from_its_notation_ends_in_the_same_position() {
+        for seed in 0..5 {
+            let mut played = game(seed);
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            for _ in 0..80 {
+                let moves = played.legal_moves();
+                if moves.is_empty() { break; }
+                played.apply_move(&moves[rng.gen_range(0..moves.len())]).unwrap();
+            }
+
+            let mut replay = game(seed);
+            for written in played.moves_notation() {
+                assert_eq!(replay.apply_notation(&written), Ok(()), "seed {}: {}", seed, written);
+            }
+            assert_eq!(replay.position_key(), played.position_key(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn notation_reads_an_ascii_arrow_and_suit_letter() {
+        let mut game = game(0);
+        assert_eq!(game.apply_notation("T7->Fd"), Ok(()));
+        assert_eq!(game.moves_notation(), ["T7→F♦"]);
+    }
+}
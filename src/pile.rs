@@ -0,0 +1,109 @@
+use crate::systems::{Card, GameState, PileRef, Stack, Tableau};
+
+// The piles cards can be picked up from, the stock is only ever drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandOrigin {
+    Talon,
+    Foundation(usize),
+    Tableau(usize)
+}
+
+// Runs are always passed bottom card first, whichever end of 'cards' a pile keeps its top at
+pub trait Pile {
+    fn cards(&self) -> &[Card];
+    fn top(&self) -> Option<&Card>;
+    fn push(&mut self, run: Vec<Card>);
+    fn pop_run(&mut self, len: usize) -> Vec<Card>;
+    fn recompute_quads(&mut self);
+}
+
+// The talon and foundations keep their top card first
+impl Pile for Stack {
+    fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    fn top(&self) -> Option<&Card> {
+        self.cards.first()
+    }
+
+    fn push(&mut self, run: Vec<Card>) {
+        for card in run {
+            self.cards.insert(0, card);
+        }
+    }
+
+    fn pop_run(&mut self, len: usize) -> Vec<Card> {
+        let mut run: Vec<Card> = self.cards.drain(..len.min(self.cards.len())).collect();
+        run.reverse();
+        run
+    }
+
+    // A stack is drawn as one quad that never moves
+    fn recompute_quads(&mut self) {}
+}
+
+// Cards taken off a tableau come from the shown ones, revealing what's left is up to the caller
+impl Pile for Tableau {
+    fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    fn top(&self) -> Option<&Card> {
+        self.cards.last()
+    }
+
+    fn push(&mut self, mut run: Vec<Card>) {
+        self.shown_cards += run.len() as u8;
+        self.cards.append(&mut run);
+        self.recompute_quads();
+    }
+
+    fn pop_run(&mut self, len: usize) -> Vec<Card> {
+        let start = self.cards.len() - len.min(self.cards.len());
+        let run: Vec<Card> = self.cards.drain(start..).collect();
+        self.shown_cards = self.shown_cards.saturating_sub(run.len() as u8);
+        self.recompute_quads();
+        run
+    }
+
+    fn recompute_quads(&mut self) {
+        self.calculate_card_quads();
+    }
+}
+
+impl GameState {
+    pub fn pile(&self, pile: PileRef) -> &dyn Pile {
+        match pile {
+            PileRef::Stock => &self.stock,
+            PileRef::Talon => &self.talon,
+            PileRef::Foundation(f) => &self.foundations[f],
+            PileRef::Tableau(t) => &self.tableaux[t]
+        }
+    }
+
+    pub fn pile_mut(&mut self, pile: PileRef) -> &mut dyn Pile {
+        match pile {
+            PileRef::Stock => &mut self.stock,
+            PileRef::Talon => &mut self.talon,
+            PileRef::Foundation(f) => &mut self.foundations[f],
+            PileRef::Tableau(t) => &mut self.tableaux[t]
+        }
+    }
+
+    pub fn hand_cards(&self) -> &[Card] {
+        &self.hand.cards
+    }
+
+    // 'None' while nothing is held
+    pub fn hand_origin_ref(&self) -> Option<HandOrigin> {
+        if self.hand.cards.is_empty() {
+            return None;
+        }
+        Some(match self.hand_origin {
+            PileRef::Foundation(f) => HandOrigin::Foundation(f),
+            PileRef::Tableau(t) => HandOrigin::Tableau(t),
+            _ => HandOrigin::Talon
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use instant::{Duration, Instant};
+
+use crate::scene::RenderItem;
+use crate::systems::GameState;
+
+// Every time the renderer has rebuilt its vertex and index buffers
+static BUFFER_REBUILDS: AtomicU64 = AtomicU64::new(0);
+
+pub fn buffer_rebuilds() -> u64 {
+    BUFFER_REBUILDS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn count_buffer_rebuild() {
+    BUFFER_REBUILDS.fetch_add(1, Ordering::Relaxed);
+}
+
+impl GameState {
+    // 'render_quads' with how long it took, the part of a buffer rebuild that grows with the board
+    pub fn render_quads_timed(&self) -> (Vec<RenderItem>, Duration) {
+        let start = Instant::now();
+        let items = self.render_quads();
+        (items, start.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::Audio;
+    use crate::rules::Rules;
+
+    #[test]
+    fn timed_quads_are_the_same_quads() {
+        let game = GameState::new_with_seed(Audio::silent(), Rules::default(), 0);
+        let (items, _) = game.render_quads_timed();
+        assert_eq!(items, game.render_quads());
+    }
+
+    #[test]
+    fn each_rebuild_is_counted() {
+        let before = buffer_rebuilds();
+        count_buffer_rebuild();
+        count_buffer_rebuild();
+        // Other tests may rebuild at the same time, the counter never goes down
+        assert!(buffer_rebuilds() >= before + 2);
+    }
+}
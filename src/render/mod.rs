@@ -64,8 +64,7 @@ impl State {
         // Surface texture format is SRGB
         let surface_format = surface_capabilities.formats.iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
+            .find(|f| f.is_srgb())
             .unwrap_or(surface_capabilities.formats[0]);
 
         let config = wgpu::SurfaceConfiguration {
@@ -151,7 +150,7 @@ impl State {
                     entry_point: "fs_main",
                     targets: &[Some(wgpu::ColorTargetState {
                         format: config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::ALL
                     })]
                 }),
@@ -207,7 +206,7 @@ impl State {
     }
 
     pub fn update(&mut self, state: &GameState) {
-        (self.vertex_buffer, self.index_buffer, self.index_count) = buffer::create_buffers(&self.device, &state);
+        (self.vertex_buffer, self.index_buffer, self.index_count) = buffer::create_buffers(&self.device, state);
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -2,6 +2,7 @@ use image::GenericImageView;
 use anyhow::*;
 
 pub struct Texture {
+    #[allow(dead_code)]
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler
@@ -0,0 +1,102 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameVariant {
+    Klondike,
+    // Klondike with every card dealt face up, so the whole deal can be planned
+    Thoughtful,
+    FreeCell { free_cells: usize }
+}
+
+impl GameVariant {
+    // One per suit per deck, every variant so far is played with a single deck
+    pub fn foundation_count(&self) -> usize {
+        match self {
+            GameVariant::Klondike | GameVariant::Thoughtful | GameVariant::FreeCell { .. } => 4
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoPolicy {
+    Free,
+    // Points taken off the score for every undo
+    ScorePenalty(i32),
+    Forbidden
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoringMode {
+    None,
+    // Points for each move, see 'move_points'
+    Standard,
+    // 52 paid for the deal, 5 back for every card that goes up
+    Vegas,
+    // Vegas with the balance of earlier games brought along in 'vegas_balance'
+    VegasCumulative
+}
+
+// What a card must have in common with the one it goes on in a column, besides being one rank lower
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableauRule {
+    // Klondike, red on black and black on red
+    AlternatingColor,
+    AnyColor,
+    SameSuit
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub variant: GameVariant,
+    // Cards turned from the stock onto the talon per click
+    pub draw_count: usize,
+    // Total passes through the stock, 'None' allows recycling the talon forever
+    pub stock_passes: Option<u32>,
+    // One pass and no more whatever 'stock_passes' says, the empty stock can't be clicked
+    pub recycle_allowed: bool,
+    // Clicking the empty stock before the last recycle only arms it,
+    // a second click within 'RECYCLE_CONFIRM_TIME' recycles
+    pub require_recycle_confirm: bool,
+    // Practice aid, the stock shows the face of the next card it will turn
+    pub peek_stock: bool,
+    // Foundations build Ace to King, or King down to Ace when false
+    pub foundation_ascending: bool,
+    // Face-up cards on each column of the deal, clamped to the column's length
+    pub initial_shown: u8,
+    pub undo_policy: UndoPolicy,
+    // Aces left on top of a column by the deal go straight up
+    pub autoplay_aces: bool,
+    // Casual play, a card turned from the stock goes straight up when it can.
+    // Never onto the tableau, that's left to the player
+    pub autoplay_on_flip: bool,
+    pub tableau_rule: TableauRule,
+    pub scoring: ScoringMode
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            variant: GameVariant::Klondike,
+            draw_count: 1,
+            stock_passes: None,
+            recycle_allowed: true,
+            require_recycle_confirm: false,
+            peek_stock: false,
+            foundation_ascending: true,
+            initial_shown: 1,
+            undo_policy: UndoPolicy::Free,
+            autoplay_aces: false,
+            autoplay_on_flip: false,
+            tableau_rule: TableauRule::AlternatingColor,
+            scoring: ScoringMode::Standard
+        }
+    }
+}
+
+impl Rules {
+    // Every card of a column counts as shown in Thoughtful, so nothing is ever left to reveal
+    pub fn dealt_face_up(&self) -> u8 {
+        match self.variant {
+            GameVariant::Thoughtful => u8::MAX,
+            _ => self.initial_shown
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use instant::Duration;
+
+use crate::audio::Audio;
+use crate::rules::Rules;
+use crate::systems::{GameState, Card, PileRef, Stack};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Malformed { line: usize, reason: String },
+    WrongCardCount { found: usize, expected: usize },
+    DuplicateCard(String),
+    FoundationOutOfOrder(usize),
+    TooManyShown(usize),
+    AlreadyWon
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+            LoadError::WrongCardCount { found, expected } => write!(f, "board has {} cards instead of {}", found, expected),
+            LoadError::DuplicateCard(card) => write!(f, "{} appears more than once", card),
+            LoadError::FoundationOutOfOrder(foundation) => write!(f, "foundation {} isn't an ascending run of one suit", foundation),
+            LoadError::TooManyShown(tableau) => write!(f, "tableau {} shows more cards than it holds", tableau),
+            LoadError::AlreadyWon => write!(f, "the game is already won")
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Every pile of a game, enough to write it out or check it's a real position
+struct Board {
+    seed: u64,
+    stock_passes_remaining: Option<u32>,
+    talon_shown: usize,
+    stock: Vec<Card>,
+    talon: Vec<Card>,
+    foundations: Vec<Vec<Card>>,
+    tableaux: Vec<(u8, Vec<Card>)>
+}
+
+impl Board {
+    fn check(&self, ascending: bool) -> Result<(), LoadError> {
+        let cards: Vec<&Card> = self.stock.iter()
+            .chain(self.talon.iter())
+            .chain(self.foundations.iter().flatten())
+            .chain(self.tableaux.iter().flat_map(|(_, cards)| cards.iter()))
+            .collect();
+
+        // A deck for every four foundations, so each card turns up that many times
+        let (expected, copies) = (self.foundations.len() * 13, self.foundations.len() / 4);
+        let mut seen = [0; 52];
+        for card in cards.iter() {
+            seen[card.value as usize] += 1;
+            if seen[card.value as usize] > copies {
+                return Err(LoadError::DuplicateCard(card.to_string()));
+            }
+        }
+        if cards.len() != expected {
+            return Err(LoadError::WrongCardCount { found: cards.len(), expected });
+        }
+
+        for (t, (shown, cards)) in self.tableaux.iter().enumerate() {
+            if *shown as usize > cards.len() {
+                return Err(LoadError::TooManyShown(t));
+            }
+        }
+
+        // Foundations keep their top card first, so the first card played is last
+        for (f, foundation) in self.foundations.iter().enumerate() {
+            for (i, card) in foundation.iter().rev().enumerate() {
+                let rank = if ascending { i } else { 12 - i };
+                if card.rank as usize != rank || card.suit != foundation[foundation.len() - 1].suit {
+                    return Err(LoadError::FoundationOutOfOrder(f));
+                }
+            }
+        }
+
+        if self.foundations.iter().all(|foundation| foundation.len() == 13) {
+            return Err(LoadError::AlreadyWon);
+        }
+
+        Ok(())
+    }
+
+    // A board only fits a game with the same number of foundations and tableaux
+    fn check_piles(&self, foundations: usize, tableaux: usize, line: usize) -> Result<(), LoadError> {
+        if self.foundations.len() != foundations || self.tableaux.len() != tableaux {
+            return Err(LoadError::Malformed {
+                line,
+                reason: format!("expected {} foundations and {} tableaux, found {} and {}",
+                    foundations, tableaux, self.foundations.len(), self.tableaux.len())
+            });
+        }
+        Ok(())
+    }
+
+    fn parse(data: &str, foundations: usize, tableaux: usize) -> Result<Board, LoadError> {
+        let mut board = Board {
+            seed: 0,
+            stock_passes_remaining: None,
+            talon_shown: 0,
+            stock: vec![],
+            talon: vec![],
+            foundations: vec![],
+            tableaux: vec![]
+        };
+
+        for (i, line) in data.lines().enumerate() {
+            let line_number = i + 1;
+            let malformed = |reason: &str| LoadError::Malformed { line: line_number, reason: reason.to_string() };
+            let mut words = line.split_whitespace();
+            let Some(key) = words.next() else { continue };
+
+            match key {
+                "seed" => {
+                    board.seed = words.next().and_then(|seed| seed.parse().ok()).ok_or_else(|| malformed("expected a seed"))?;
+                },
+                "passes" => {
+                    board.stock_passes_remaining = match words.next() {
+                        Some("-") => None,
+                        Some(passes) => Some(passes.parse().map_err(|_| malformed("expected a number of passes or '-'"))?),
+                        None => return Err(malformed("expected a number of passes or '-'"))
+                    };
+                },
+                "talon_shown" => {
+                    board.talon_shown = words.next().and_then(|shown| shown.parse().ok()).ok_or_else(|| malformed("expected a number of shown cards"))?;
+                },
+                "stock" => board.stock = Board::parse_cards(words, line_number)?,
+                "talon" => board.talon = Board::parse_cards(words, line_number)?,
+                "foundation" => board.foundations.push(Board::parse_cards(words, line_number)?),
+                "tableau" => {
+                    let shown = words.next().and_then(|shown| shown.parse().ok()).ok_or_else(|| malformed("expected a number of shown cards"))?;
+                    board.tableaux.push((shown, Board::parse_cards(words, line_number)?));
+                },
+                _ => return Err(malformed(&format!("unknown key '{}'", key)))
+            }
+        }
+
+        board.check_piles(foundations, tableaux, data.lines().count())?;
+
+        Ok(board)
+    }
+
+    // Which foundation a suit was started on doesn't change the position, so they're
+    // taken in suit order with the empty ones last
+    fn foundations_by_suit(&self) -> Vec<&Vec<Card>> {
+        let mut foundations: Vec<&Vec<Card>> = self.foundations.iter().collect();
+        foundations.sort_by_key(|foundation| foundation.first().map_or(4, |card| card.value / 13));
+        foundations
+    }
+
+    // One line, fields split by '|': the stock from the bottom, the talon from the top,
+    // the top card of each foundation in suit order and every tableau from the bottom
+    // with hidden cards marked by '#', e.g. 'S:Kc,3d|T:Ah|F:2s,,,|TB0:#Qh,Js|...|TB6:'
+    fn signature(&self) -> String {
+        let cards = |cards: &[Card]| cards.iter().map(|card| card.to_string()).collect::<Vec<_>>().join(",");
+
+        let mut fields = vec![
+            format!("S:{}", cards(&self.stock)),
+            format!("T:{}", cards(&self.talon)),
+            format!("F:{}", self.foundations_by_suit().iter()
+                .map(|foundation| foundation.first().map(|card| card.to_string()).unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join(","))
+        ];
+        for (t, (shown, tableau)) in self.tableaux.iter().enumerate() {
+            let first_shown = tableau.len() - *shown as usize;
+            let tableau = tableau.iter().enumerate()
+                .map(|(i, card)| if i < first_shown { format!("#{}", card) } else { card.to_string() })
+                .collect::<Vec<_>>()
+                .join(",");
+            fields.push(format!("TB{}:{}", t, tableau));
+        }
+        fields.join("|")
+    }
+
+    fn from_signature(signature: &str, ascending: bool, foundations: usize, tableaux: usize) -> Result<Board, LoadError> {
+        let malformed = |reason: String| LoadError::Malformed { line: 1, reason };
+        let parse_card = |field: &str, card: &str| card.parse::<Card>().map_err(|e| malformed(format!("{}: {}", field, e)));
+        let parse_cards = |field: &str, cards: &str| -> Result<Vec<Card>, LoadError> {
+            cards.split(',').filter(|card| !card.is_empty()).map(|card| parse_card(field, card)).collect()
+        };
+
+        let mut board = Board {
+            seed: 0,
+            stock_passes_remaining: None,
+            talon_shown: 0,
+            stock: vec![],
+            talon: vec![],
+            foundations: vec![],
+            tableaux: vec![]
+        };
+
+        for field in signature.trim().split('|') {
+            let (key, value) = field.split_once(':').ok_or_else(|| malformed(format!("field '{}' has no ':'", field)))?;
+            match key {
+                "S" => board.stock = parse_cards(key, value)?,
+                "T" => board.talon = parse_cards(key, value)?,
+                "F" => {
+                    // Only the tops are written, everything under them follows from the rules
+                    for top in value.split(',') {
+                        let mut foundation = vec![];
+                        if !top.is_empty() {
+                            let top = parse_card(key, top)?;
+                            let ranks: Vec<u8> = if ascending { (0..=top.rank).rev().collect() } else { (top.rank..13).collect() };
+                            for rank in ranks {
+                                foundation.push(Card::new(top.value - top.rank + rank));
+                            }
+                        }
+                        board.foundations.push(foundation);
+                    }
+                },
+                _ if key.starts_with("TB") => {
+                    if key[2..].parse() != Ok(board.tableaux.len()) {
+                        return Err(malformed(format!("expected tableau {} but found '{}'", board.tableaux.len(), key)));
+                    }
+                    let mut shown = 0;
+                    let mut cards = vec![];
+                    for card in value.split(',').filter(|card| !card.is_empty()) {
+                        match card.strip_prefix('#') {
+                            Some(_) if shown > 0 => return Err(malformed(format!("{}: hidden card '{}' on top of a shown one", key, card))),
+                            Some(hidden) => cards.push(parse_card(key, hidden)?),
+                            None => {
+                                cards.push(parse_card(key, card)?);
+                                shown += 1;
+                            }
+                        }
+                    }
+                    board.tableaux.push((shown, cards));
+                },
+                _ => return Err(malformed(format!("unknown field '{}'", key)))
+            }
+        }
+
+        board.check_piles(foundations, tableaux, 1)?;
+        board.talon_shown = board.talon.len().min(1);
+
+        Ok(board)
+    }
+
+    fn parse_cards<'a>(words: impl Iterator<Item = &'a str>, line: usize) -> Result<Vec<Card>, LoadError> {
+        words.map(|word| word.parse::<Card>().map_err(|e| LoadError::Malformed { line, reason: e.to_string() }))
+            .collect()
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cards = |cards: &[Card]| cards.iter().map(|card| format!(" {}", card)).collect::<String>();
+
+        writeln!(f, "seed {}", self.seed)?;
+        match self.stock_passes_remaining {
+            Some(passes) => writeln!(f, "passes {}", passes)?,
+            None => writeln!(f, "passes -")?
+        }
+        writeln!(f, "talon_shown {}", self.talon_shown)?;
+        writeln!(f, "stock{}", cards(&self.stock))?;
+        writeln!(f, "talon{}", cards(&self.talon))?;
+        for foundation in self.foundations.iter() {
+            writeln!(f, "foundation{}", cards(foundation))?;
+        }
+        for (shown, tableau) in self.tableaux.iter() {
+            writeln!(f, "tableau {}{}", shown, cards(tableau))?;
+        }
+        Ok(())
+    }
+}
+
+impl GameState {
+    // Held cards are saved back where they were picked up from
+    fn board(&self) -> Board {
+        let mut board = Board {
+            seed: self.seed(),
+            stock_passes_remaining: self.stock_passes_remaining,
+            talon_shown: self.talon_shown,
+            stock: self.stock.cards.clone(),
+            talon: self.talon.cards.clone(),
+            foundations: self.foundations.iter().map(|foundation| foundation.cards.clone()).collect(),
+            tableaux: self.tableaux.iter().map(|tableau| (tableau.shown_cards, tableau.cards.clone())).collect()
+        };
+
+        if !self.hand.cards.is_empty() {
+            match self.hand_origin {
+                PileRef::Stock | PileRef::Talon => board.talon.insert(0, self.hand.cards[0].clone()),
+                PileRef::Foundation(f) => board.foundations[f].insert(0, self.hand.cards[0].clone()),
+                PileRef::Tableau(t) => {
+                    let (shown, cards) = &mut board.tableaux[t];
+                    *shown += self.hand.cards.len() as u8;
+                    cards.extend(self.hand.cards.iter().cloned());
+                }
+            }
+        }
+
+        board
+    }
+
+    pub fn save(&self) -> String {
+        self.board().to_string()
+    }
+
+    // Same for any two games with the same cards in the same places, however they got there.
+    // The deal's seed isn't part of the position, nor which foundation each suit went on
+    pub fn position_key(&self) -> u64 {
+        let board = self.board();
+        let values = |cards: &[Card]| cards.iter().map(|card| card.value).collect::<Vec<u8>>();
+
+        let mut hasher = DefaultHasher::new();
+        board.stock_passes_remaining.hash(&mut hasher);
+        board.talon_shown.hash(&mut hasher);
+        values(&board.stock).hash(&mut hasher);
+        values(&board.talon).hash(&mut hasher);
+        for foundation in board.foundations_by_suit() {
+            values(foundation).hash(&mut hasher);
+        }
+        for (shown, tableau) in board.tableaux.iter() {
+            shown.hash(&mut hasher);
+            values(tableau).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    pub fn validate(&self) -> Result<(), LoadError> {
+        self.board().check(self.rules.foundation_ascending)
+    }
+
+    pub fn board_signature(&self) -> String {
+        self.board().signature()
+    }
+
+    // Rebuilds the position from a bug report, the deal it came from isn't part of the
+    // signature so the seed is left as a fresh one
+    pub fn new_from_signature(signature: &str, audio: Audio) -> Result<Self, LoadError> {
+        let mut state = GameState::new(audio);
+        let mut board = Board::from_signature(signature, state.rules.foundation_ascending, state.foundations.len(), state.tableaux.len())?;
+        board.check(state.rules.foundation_ascending)?;
+
+        board.seed = state.seed();
+        board.stock_passes_remaining = state.stock_passes_remaining;
+        state.set_board(board);
+        Ok(state)
+    }
+
+    // Starts with cards already up, for trying out positions near the end of a game. Each
+    // foundation lists card values in the order they're played, one list for each foundation
+    // the rules lay out. The rest of the deck is dealt as usual, columns that run out of cards
+    // are left short or empty
+    pub fn new_with_foundations(audio: Audio, rules: Rules, seed: u64, foundations: Vec<Vec<u8>>) -> Result<Self, LoadError> {
+        let mut state = GameState::new_with_seed(audio, rules, seed);
+        if foundations.len() != state.foundations.len() {
+            return Err(LoadError::Malformed {
+                line: 0,
+                reason: format!("expected {} foundations, found {}", state.foundations.len(), foundations.len())
+            });
+        }
+
+        // Copies of each card already up, that many are left out of the deck
+        let mut played = [0usize; 52];
+        for value in foundations.iter().flatten() {
+            match played.get_mut(*value as usize) {
+                Some(count) => *count += 1,
+                None => return Err(LoadError::Malformed { line: 0, reason: format!("{} isn't a card", value) })
+            }
+        }
+        let mut deck = Stack::seeded_deck(seed).cards;
+        deck.retain(|card| {
+            let count = &mut played[card.value as usize];
+            let keep = *count == 0;
+            *count = count.saturating_sub(1);
+            keep
+        });
+
+        let face_up = state.rules.dealt_face_up();
+        let tableaux = (0..state.tableaux.len()).map(|i| {
+            let cards: Vec<Card> = deck.drain(..(i + 1).min(deck.len())).collect();
+            let shown = if cards.is_empty() { 0 } else { face_up.clamp(1, cards.len() as u8) };
+            (shown, cards)
+        }).collect();
+
+        let board = Board {
+            seed,
+            stock_passes_remaining: state.stock_passes_remaining,
+            talon_shown: 0,
+            stock: deck,
+            talon: vec![],
+            foundations: foundations.iter()
+                .map(|foundation| foundation.iter().rev().map(|value| Card::new(*value)).collect())
+                .collect(),
+            tableaux
+        };
+        board.check(state.rules.foundation_ascending)?;
+        state.set_board(board);
+        Ok(state)
+    }
+
+    // Saves to 'autosave_path' when something has changed, unless the last save was too recent
+    pub(crate) fn update_autosave(&mut self) {
+        if !self.autosave_pending { return; }
+        if let Some(at) = self.autosaved_at {
+            if self.game_time - at < AUTOSAVE_INTERVAL { return; }
+        }
+        self.flush_autosave();
+    }
+
+    // Writes any change not yet autosaved straight away, for when the game closes
+    pub fn flush_autosave(&mut self) {
+        if !self.autosave_pending { return; }
+        let Some(path) = &self.settings.autosave_path else { return };
+        if let Err(e) = std::fs::write(path, self.save()) {
+            log::warn!("Couldn't autosave to '{}': {}", path.display(), e);
+        }
+        self.autosave_pending = false;
+        self.autosaved_at = Some(self.game_time);
+    }
+
+    // The current game is only replaced once the save is known to be a real position
+    pub fn load(&mut self, data: &str) -> Result<(), LoadError> {
+        let board = Board::parse(data, self.foundations.len(), self.tableaux.len())?;
+        board.check(self.rules.foundation_ascending)?;
+        self.set_board(board);
+        Ok(())
+    }
+
+    fn set_board(&mut self, board: Board) {
+        self.seed = board.seed;
+        self.stock_passes_remaining = board.stock_passes_remaining;
+        self.talon_shown = board.talon_shown.min(board.talon.len());
+        self.stock.cards = board.stock;
+        self.talon.cards = board.talon;
+        for (foundation, cards) in self.foundations.iter_mut().zip(board.foundations) {
+            foundation.cards = cards;
+        }
+        for (tableau, (shown, cards)) in self.tableaux.iter_mut().zip(board.tableaux) {
+            tableau.shown_cards = shown;
+            tableau.cards = cards;
+            tableau.calculate_card_quads();
+        }
+        self.hand.cards.clear();
+        // The score and clock belonged to the game being replaced, none of it is saved
+        self.history.clear();
+        self.score = 0;
+        self.mistakes = 0;
+        self.hint_cycle = None;
+        self.reset_clock();
+        self.winning_move = None;
+        self.cascade = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+
+    fn game(seed: u64) -> GameState {
+        GameState::new_with_seed(Audio::silent(), Rules::default(), seed)
+    }
+
+    #[test]
+    fn a_save_loads_back_into_the_same_position() {
+        let mut game = game(0);
+        game.apply_move(&Move::FlipStock).unwrap();
+        let save = game.save();
+
+        let mut loaded = GameState::new_with_seed(Audio::silent(), Rules::default(), 1);
+        assert_eq!(loaded.load(&save), Ok(()));
+        assert_eq!(loaded.validate(), Ok(()));
+        assert_eq!(loaded.position_key(), game.position_key());
+        assert_eq!(loaded.seed(), 0);
+    }
+
+    // Seed 0 as a board for two decks, a second deck in the stock and eight empty foundations
+    fn double_deck_board() -> Board {
+        let game = game(0);
+        Board {
+            seed: 0,
+            stock_passes_remaining: None,
+            talon_shown: 0,
+            stock: game.stock.cards.iter().chain(&Stack::seeded_deck(1).cards).cloned().collect(),
+            talon: vec![],
+            foundations: vec![vec![]; 8],
+            tableaux: game.tableaux.iter().map(|tableau| (tableau.shown_cards, tableau.cards.clone())).collect()
+        }
+    }
+
+    #[test]
+    fn two_decks_make_a_board_of_every_card_twice() {
+        let mut board = double_deck_board();
+        assert_eq!(board.check(true), Ok(()));
+
+        board.stock.pop();
+        assert_eq!(board.check(true), Err(LoadError::WrongCardCount { found: 103, expected: 104 }));
+        board.stock.push(board.stock[0].clone());
+        assert_eq!(board.check(true), Err(LoadError::DuplicateCard(board.stock[0].to_string())));
+    }
+
+    #[test]
+    fn a_save_with_a_card_twice_is_rejected() {
+        // Seed 0 deals the 4♦ alone on the first column, the A♠ is somewhere else
+        let save = game(0).save().replace("tableau 1 4d", "tableau 1 As");
+        let mut game = game(1);
+        let before = game.save();
+        assert_eq!(game.load(&save), Err(LoadError::DuplicateCard("As".to_string())));
+        assert_eq!(game.save(), before);
+    }
+
+    #[test]
+    fn loading_starts_the_score_and_clock_over() {
+        let mut game = game(0);
+        let save = game.save();
+        game.score = 40;
+        game.mistakes = 3;
+        game.tick_fixed(5.0);
+        game.apply_move(&Move::FlipStock).unwrap();
+
+        game.load(&save).unwrap();
+        assert_eq!(game.score(), 0);
+        assert_eq!(game.mistakes(), 0);
+        assert_eq!(game.game_time, Duration::ZERO);
+        assert!(game.move_timeline().is_empty());
+    }
+
+    #[test]
+    fn position_key_ignores_which_foundation_a_suit_is_on() {
+        let (mut first, mut third) = (game(0), game(0));
+        // Seed 0 deals the A♦ on top of the last column
+        first.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        third.apply_move(&Move::TableauToFoundation { from: 6, to: 2 }).unwrap();
+        assert_eq!(first.position_key(), third.position_key());
+        assert_eq!(first.board_signature(), third.board_signature());
+    }
+
+    fn all_values(game: &GameState) -> Vec<u8> {
+        let mut values: Vec<u8> = game.stock.cards.iter()
+            .chain(&game.talon.cards)
+            .chain(game.foundations.iter().flat_map(|foundation| &foundation.cards))
+            .chain(game.tableaux.iter().flat_map(|tableau| &tableau.cards))
+            .map(|card| card.value)
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn foundations_can_start_partly_built() {
+        let foundations = vec![vec![0, 1, 2], vec![], vec![13], vec![]];
+        let game = GameState::new_with_foundations(Audio::silent(), Rules::default(), 0, foundations).unwrap();
+        assert_eq!(game.foundations[0].cards[0].to_string(), "3s");
+        assert_eq!(game.foundation_progress(), 4);
+        assert_eq!(all_values(&game), (0..52).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn foundations_that_dont_build_up_are_rejected() {
+        // The 2♠ without the A♠ under it
+        let foundations = vec![vec![1], vec![], vec![], vec![]];
+        assert!(GameState::new_with_foundations(Audio::silent(), Rules::default(), 0, foundations).is_err());
+        let foundations = vec![vec![0, 14], vec![], vec![], vec![]];
+        assert!(GameState::new_with_foundations(Audio::silent(), Rules::default(), 0, foundations).is_err());
+    }
+
+    #[test]
+    fn autosaves_catch_up_with_the_latest_move() {
+        let path = std::env::temp_dir().join(format!("solitaire_autosave_{}", std::process::id()));
+        let mut game = game(0);
+        game.settings.autosave_path = Some(path.clone());
+
+        game.apply_move(&Move::FlipStock).unwrap();
+        game.tick_fixed(0.1);
+        let first = game.save();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), first);
+
+        // Too soon after the last save, the file waits
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        game.tick_fixed(0.1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), first);
+
+        game.apply_move(&Move::FlipStock).unwrap();
+        game.tick_fixed(1.0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), game.save());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn foundations_must_match_the_rules() {
+        let foundations = vec![vec![0], vec![13], vec![26]];
+        assert!(GameState::new_with_foundations(Audio::silent(), Rules::default(), 0, foundations).is_err());
+    }
+
+    #[test]
+    fn a_save_missing_a_pile_is_rejected() {
+        // Saves end with the tableaux, so this drops the last one
+        let save = game(0).save();
+        let short = save.trim_end().rsplit_once('\n').unwrap().0;
+        let mut game = game(1);
+        assert!(matches!(game.load(short), Err(LoadError::Malformed { .. })));
+    }
+}
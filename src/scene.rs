@@ -0,0 +1,187 @@
+use crate::systems::{GameState, FoundationHint, PileRef, Quad, Stack, Card, Vec2};
+
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const FLASH_RED: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+pub const DROP_GREEN: [f32; 4] = [0.8, 1.0, 0.8, 1.0];
+pub const DROP_RED: [f32; 4] = [1.0, 0.8, 0.8, 1.0];
+const OUTLINE_TINT: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+const GHOST_TINT: [f32; 4] = [1.0, 1.0, 1.0, 0.35];
+
+// The blank card, drawn for empty piles and tinted dark as an outline
+const EMPTY_INDEX: [u8; 2] = [1, 4];
+const OUTLINE_INDEX: [u8; 2] = [1, 4];
+const SUIT_HINT_INDEX: [u8; 2] = [2, 4];
+const ACE_HINT_INDEX: [u8; 2] = [6, 4];
+// Pass dots under the stock follow the hints on the last row, filled then hollow
+const PASS_DOT_INDEX: [u8; 2] = [7, 4];
+const USED_PASS_DOT_INDEX: [u8; 2] = [8, 4];
+
+// Layers, higher draws on top. Cards sliding somewhere are lifted over the board
+// and held cards are above everything
+const ANIMATING_Z: u32 = 100;
+const HAND_Z: u32 = 200;
+const CASCADE_Z: u32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderItem {
+    pub quad: Quad,
+    pub sprite: [u8; 2],
+    pub tint: [f32; 4],
+    // Items with equal z keep the order they were returned in
+    pub z: u32
+}
+
+impl GameState {
+    // Everything to draw this frame, sorted back to front
+    pub fn render_quads(&self) -> Vec<RenderItem> {
+        let mut items = vec![];
+        let card_back = self.render_config.card_back_index;
+
+        let stock_index = match self.stock.cards.last() {
+            None => EMPTY_INDEX,
+            Some(card) if self.rules.peek_stock => index_from_card(card),
+            Some(_) => card_back
+        };
+        self.push_card(&mut items, self.stock.quad, stock_index, WHITE, 0);
+        self.push_pass_dots(&mut items);
+        if self.talon.cards.is_empty() {
+            self.push_card(&mut items, self.talon.quad, EMPTY_INDEX, WHITE, 0);
+        } else {
+            // Fanned from the oldest card of the draw on the left to the playable top on the right
+            let shown = self.talon_shown.max(1).min(self.talon.cards.len());
+            for i in (0..shown).rev() {
+                let card = &self.talon.cards[i];
+                let quad = Quad {
+                    pos: Vec2 {
+                        x: self.talon.quad.pos.x + (shown - 1 - i) as f32 * self.layout.talon_fan_offset,
+                        y: self.talon.quad.pos.y
+                    },
+                    size: self.talon.quad.size
+                };
+                self.push_card(&mut items, self.animated_quad(card, &quad), index_from_card(card), WHITE, self.card_z(card, shown - i));
+            }
+        }
+
+        for (t, tableau) in self.tableaux.iter().enumerate() {
+            let fan_offset = if self.hovered_tableau() == Some(t) { self.layout.hover_fan_offset } else { 0.0 };
+            if tableau.cards.is_empty() {
+                self.push_card(&mut items, tableau.card_quads[0], EMPTY_INDEX, WHITE, 0);
+            } else {
+                let first_shown = self.tableau_split(t).0.len();
+                for (i, card) in tableau.cards.iter().enumerate() {
+                    let mut quad = self.animated_quad(card, &tableau.card_quads[i]);
+                    quad.pos.y -= i.saturating_sub(first_shown) as f32 * fan_offset;
+                    let sprite = if i >= first_shown { index_from_card(card) } else { card_back };
+                    self.push_card(&mut items, quad, sprite, WHITE, self.card_z(card, i + 1));
+                }
+            }
+        }
+
+        for (f, stack) in self.foundations.iter().enumerate() {
+            let tint = if self.foundation_flash() == Some(f) { FLASH_RED } else { WHITE };
+            let z = stack.cards.first().map_or(0, |card| self.card_z(card, 1));
+            let sprite = match (stack.cards.len(), self.render_config.foundation_hint) {
+                (0, FoundationHint::SuitSymbol) => [SUIT_HINT_INDEX[0] + f as u8, SUIT_HINT_INDEX[1]],
+                (0, FoundationHint::AceOutline) => ACE_HINT_INDEX,
+                _ => stack_index(stack)
+            };
+            self.push_card(&mut items, self.stack_quad(stack), sprite, tint, z);
+        }
+
+        // Faint copies of held cards where they were lifted from, as a reminder of where to put them back
+        if let (PileRef::Tableau(t), true) = (self.hand_origin, !self.hand.cards.is_empty()) {
+            let tableau = &self.tableaux[t];
+            for (i, card) in self.hand.cards.iter().enumerate() {
+                let depth = tableau.cards.len() + i;
+                let quad = Quad {
+                    pos: Vec2::new(tableau.x_position, tableau.y_position - depth as f32 * tableau.stack_offset),
+                    size: self.card_size()
+                };
+                items.push(RenderItem { quad, sprite: index_from_card(card), tint: GHOST_TINT, z: depth as u32 + 1 });
+            }
+        }
+
+        let hand_tint = match self.hand_drop_legal() {
+            Some(true) => DROP_GREEN,
+            Some(false) => DROP_RED,
+            None => WHITE
+        };
+        for (i, card) in self.hand.cards.iter().enumerate() {
+            let quad = Quad {
+                pos: Vec2 {
+                    x: self.hand.quad.pos.x,
+                    y: self.hand.quad.pos.y -(i as f32 * self.layout.stack_offset)
+                },
+                size: self.card_size()
+            };
+            self.push_card(&mut items, quad, index_from_card(card), hand_tint, HAND_Z + i as u32);
+        }
+
+        if let Some(cascade) = &self.cascade {
+            for card in cascade.cards.iter() {
+                let quad = Quad::new(card.pos, self.card_size());
+                items.push(RenderItem { quad, sprite: index_from_card(&Card::new(card.value)), tint: WHITE, z: CASCADE_Z });
+            }
+        }
+
+        items.sort_by_key(|item| item.z);
+        items
+    }
+
+    // A row of dots under the stock with limited passes, one per pass, hollow once it's used
+    fn push_pass_dots(&self, items: &mut Vec<RenderItem>) {
+        let Some(left) = self.passes_left() else { return };
+        let total = self.rules.stock_passes.unwrap_or(left).max(left);
+
+        let spacing = self.layout.pass_dot_spacing;
+        let y = self.stock.quad.bottom() - spacing;
+        let first_x = self.stock.quad.pos.x - (total - 1) as f32 * spacing / 2.0;
+        for i in 0..total {
+            let quad = Quad::new(Vec2::new(first_x + i as f32 * spacing, y), Vec2::new(self.layout.pass_dot_size, self.layout.pass_dot_size));
+            let sprite = if i < left { PASS_DOT_INDEX } else { USED_PASS_DOT_INDEX };
+            items.push(RenderItem { quad, sprite, tint: WHITE, z: 0 });
+        }
+    }
+
+    // 'depth' orders cards within a pile, a card that's sliding keeps it but above every pile
+    fn card_z(&self, card: &Card, depth: usize) -> u32 {
+        let z = depth as u32;
+        if self.is_animating(card) { ANIMATING_Z + z } else { z }
+    }
+
+    fn push_card(&self, items: &mut Vec<RenderItem>, quad: Quad, sprite: [u8; 2], tint: [f32; 4], z: u32) {
+        if let Some(width) = self.render_config.card_outline {
+            let outline = Quad {
+                pos: quad.pos,
+                size: Vec2::new(quad.size.x + width * 2.0, quad.size.y + width * 2.0)
+            };
+            items.push(RenderItem { quad: outline, sprite: OUTLINE_INDEX, tint: OUTLINE_TINT, z });
+        }
+        items.push(RenderItem { quad, sprite, tint, z });
+    }
+
+    fn animated_quad(&self, card: &Card, quad: &Quad) -> Quad {
+        Quad {
+            pos: self.animated_pos(card, quad.pos),
+            size: quad.size
+        }
+    }
+
+    fn stack_quad(&self, stack: &Stack) -> Quad {
+        match stack.cards.first() {
+            Some(card) => self.animated_quad(card, &stack.quad),
+            None => stack.quad
+        }
+    }
+}
+
+fn stack_index(stack: &Stack) -> [u8; 2] {
+    match stack.cards.first() {
+        Some(card) => index_from_card(card),
+        None => EMPTY_INDEX
+    }
+}
+
+fn index_from_card(card: &Card) -> [u8; 2] {
+    [card.value % 13, card.value / 13]
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+use instant::Duration;
+
+// Player preferences, unlike 'Rules' these never change how the game plays
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    // Stops the clock while the window is in the background
+    pub auto_pause_on_unfocus: bool,
+    // Cards follow the cursor while the button is held and drop on release,
+    // instead of a click to pick up and another to put down
+    pub drag_and_drop: bool,
+    // How far the cursor must move with the button held before a drag starts,
+    // anything less is a click
+    pub drag_threshold_px: f32,
+    // When off cards jump straight to where they go and the hand sticks to the cursor.
+    // Off by default, moves are instant until the animations are polished
+    pub animations_enabled: bool,
+    // How long a card that's just been turned over ignores clicks
+    pub reveal_delay: Duration,
+    // Bounces the foundations off the bottom of the screen once the game is won
+    pub win_cascade: bool,
+    // Each placing sound plays up to this much faster or slower, 0.1 is within 10%
+    pub pitch_variation: f32,
+    // Where the game is written after moves, at most once every 'AUTOSAVE_INTERVAL'
+    pub autosave_path: Option<PathBuf>
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            auto_pause_on_unfocus: false,
+            drag_and_drop: false,
+            drag_threshold_px: 8.0,
+            animations_enabled: false,
+            reveal_delay: Duration::ZERO,
+            win_cascade: false,
+            pitch_variation: 0.0,
+            autosave_path: None
+        }
+    }
+}
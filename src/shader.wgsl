@@ -1,12 +1,14 @@
 // Vertex shader
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) tint: vec4<f32>
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>
 };
 
 @vertex
@@ -15,6 +17,7 @@ fn vs_main  (
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
+    out.tint = model.tint;
     out.clip_position = vec4<f32>(model.position, 1.0);
     return out;
 }
@@ -28,5 +31,5 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
 }
\ No newline at end of file
@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+use crate::systems::{GameState, Card, Color, Quad, Vec2};
+
+const CORNER_RADIUS: f32 = 12.0;
+const FONT_SIZE: f32 = 40.0;
+
+impl GameState {
+    // The board as drawn, with plain shapes and card names instead of the sprite sheet.
+    // World space is kept as is, flipped so y points down like SVG expects
+    pub fn to_svg(&self) -> String {
+        let width = self.viewport.x * 2;
+        let height = self.viewport.y * 2;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#1b6b34\"/>\n",
+            -self.viewport.x, -self.viewport.y, width, height,
+            -self.viewport.x, -self.viewport.y, width, height
+        );
+
+        match self.stock.cards.len() {
+            0 => empty_slot(&mut svg, &self.stock.quad),
+            _ => face_down(&mut svg, &self.stock.quad)
+        }
+
+        let shown = self.talon_shown.max(1).min(self.talon.cards.len());
+        if shown == 0 {
+            empty_slot(&mut svg, &self.talon.quad);
+        }
+        for i in (0..shown).rev() {
+            let pos = Vec2::new(self.talon.quad.pos.x + (shown - 1 - i) as f32 * self.layout.talon_fan_offset, self.talon.quad.pos.y);
+            face_up(&mut svg, &Quad::new(pos, self.talon.quad.size), &self.talon.cards[i]);
+        }
+
+        for foundation in self.foundations.iter() {
+            match foundation.cards.first() {
+                Some(card) => face_up(&mut svg, &foundation.quad, card),
+                None => empty_slot(&mut svg, &foundation.quad)
+            }
+        }
+
+        for (t, tableau) in self.tableaux.iter().enumerate() {
+            let (hidden, _) = self.tableau_split(t);
+            if tableau.cards.is_empty() {
+                empty_slot(&mut svg, &tableau.card_quads[0]);
+            }
+            for (i, card) in tableau.cards.iter().enumerate() {
+                if i < hidden.len() {
+                    face_down(&mut svg, &tableau.card_quads[i]);
+                } else {
+                    face_up(&mut svg, &tableau.card_quads[i], card);
+                }
+            }
+        }
+
+        for (i, card) in self.hand.cards.iter().enumerate() {
+            let pos = Vec2::new(self.hand.quad.pos.x, self.hand.quad.pos.y - i as f32 * self.layout.stack_offset);
+            face_up(&mut svg, &Quad::new(pos, self.card_size()), card);
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn card_rect(svg: &mut String, quad: &Quad, fill: &str, stroke: &str) {
+    let _ = writeln!(svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"3\"/>",
+        quad.left(), -quad.top(), quad.size.x, quad.size.y, CORNER_RADIUS, fill, stroke
+    );
+}
+
+fn empty_slot(svg: &mut String, quad: &Quad) {
+    card_rect(svg, quad, "none", "#d0d0d0");
+}
+
+fn face_down(svg: &mut String, quad: &Quad) {
+    card_rect(svg, quad, "#808080", "#404040");
+}
+
+// The name sits in the top left corner, which is all that shows of a card lower in a column
+fn face_up(svg: &mut String, quad: &Quad, card: &Card) {
+    card_rect(svg, quad, "#ffffff", "#404040");
+    let colour = if card.color == Color::Red { "#c0392b" } else { "#000000" };
+    let _ = writeln!(svg,
+        "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" fill=\"{}\">{}</text>",
+        quad.left() + CORNER_RADIUS, -quad.top() + FONT_SIZE, FONT_SIZE, colour, card
+    );
+}
@@ -1,16 +1,33 @@
-use std::{ops::{Mul, AddAssign}, vec, thread};
-use std::io::Cursor;
-use std::fs::File;
-use std::io::BufReader;
-use rodio::{Decoder, OutputStream, Sink, OutputStreamHandle, source::Buffered};
+use std::{ops::{Mul, Add, Sub, AddAssign}, vec, fmt, str::FromStr};
 use instant::Duration;
-use rand::Rng;
-use winit::event::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use winit::{event::*, dpi::PhysicalPosition};
+
+use crate::animation::{CardAnimation, WinCascade, SNAP_TIME};
+use crate::audio::{Audio, Sound};
+use crate::controls::{Action, KeyBindings};
+use crate::events::{GameEvent, InvalidReason};
+use crate::moves::{Move, MoveError, MoveRecord};
+use crate::pile::Pile;
+use crate::rules::{Rules, GameVariant, ScoringMode, TableauRule};
+use crate::settings::Settings;
 
 pub const SCREEN_SIZE: Vec2i = Vec2i {x: 1200, y:900};
 
 pub const CARD_SIZE: Vec2 = Vec2 { x: 160.0, y: 240.0 };
 
+pub const STACK_OFFSET: f32 = 70.0;
+
+pub const TALON_FAN_OFFSET: f32 = 30.0;
+
+// Added between face-up cards of the column under the cursor, only when drawing
+pub const HOVER_FAN_OFFSET: f32 = 15.0;
+
+// The dots under the stock counting passes left
+pub const PASS_DOT_SIZE: f32 = 20.0;
+pub const PASS_DOT_SPACING: f32 = 30.0;
+
 pub const DECK_QUAD: Quad = Quad {
     pos: Vec2 { x: -700.0, y: 350.0 },
     size: CARD_SIZE
@@ -19,17 +36,221 @@ pub const DECK_QUAD: Quad = Quad {
 const TICKS_PER_SECOND: f32 = 60.0;
 const TICK_TIME: f32 = 1.0 / TICKS_PER_SECOND;
 
+const RECYCLE_CONFIRM_TIME: f32 = 2.0;
+
+const FLASH_TIME: f32 = 0.3;
+
+// Below this the held cards are considered at rest and snap onto the cursor
+const HAND_SETTLE_EPSILON: f32 = 0.5;
+
+// World units the second click of a double click may be from the first, so a drag isn't one
+const DOUBLE_CLICK_RADIUS: f32 = 20.0;
+
+const WORLD_UNITS_PER_PIXEL: f64 = 2.0;
+
+const VEGAS_BUY_IN: i32 = 52;
+const VEGAS_CARD_PAYOUT: i32 = 5;
+
+// Weight of the newest frame in the averaged frame time behind 'fps'
+const FPS_SMOOTHING: f32 = 0.1;
+
 pub struct GameState {
     pub stock: Stack,
     pub talon: Stack,
     pub tableaux: [Tableau; 7],
-    pub foundations: [Stack; 4],
+    pub foundations: Vec<Stack>,
     pub hand: Stack,
-    hand_origin: u8,
+    pub rules: Rules,
+    pub settings: Settings,
+    pub layout: BoardLayout,
+    // Size in pixels of the window the board is drawn in
+    pub viewport: Vec2i,
+    pub(crate) seed: u64,
+    pub render_config: RenderConfig,
+    pub key_bindings: KeyBindings,
+    pub stock_passes_remaining: Option<u32>,
+    // How many cards of the last draw are fanned out on the talon, only the top is playable
+    pub talon_shown: usize,
+    pub spring_stiffness: f32,
+    pub damping: f32,
+    // Attract mode, random moves are played after 'DEMO_IDLE_TIME' without input
+    pub demo_enabled: bool,
+    // Longest gap between the clicks of a double click
+    pub double_click_ms: u64,
+    pub(crate) hand_origin: PileRef,
+    pub(crate) history: Vec<MoveRecord>,
+    pub(crate) game_time: Duration,
+    pub(crate) score: i32,
+    // What earlier games left in the Vegas balance, set when dealing the next one
+    pub vegas_balance: i32,
+    // The move that put the last card up, once the game is won
+    pub(crate) winning_move: Option<Move>,
+    // The position 'next_hint' was last asked about and which hint it gave
+    pub(crate) hint_cycle: Option<(u64, usize)>,
+    // A move has been made since the last autosave, and when that was
+    pub(crate) autosave_pending: bool,
+    pub(crate) autosaved_at: Option<Duration>,
+    // Cards turned over within the last 'reveal_delay', with when
+    recent_reveals: Vec<(u8, Duration)>,
+    pub(crate) mistakes: u32,
+    // 'talon_shown' when the move being made started, held cards may have changed it since
+    pub(crate) talon_shown_before: usize,
+    // Seconds since the last input, and when on that clock the demo last moved
+    pub(crate) idle_time: f32,
+    pub(crate) demo_played_at: f32,
+    pub(crate) demo_stock_moves: usize,
+    pub(crate) demo_rng: ChaCha8Rng,
+    // Only for 'pitch_variation', so sounds don't change how a replay's demo plays
+    sound_rng: ChaCha8Rng,
+    hand_velocity: Vec2,
+    paused: bool,
+    last_click: Option<(Duration, Vec2)>,
+    // Where the left button went down in drag and drop mode, until it's released
+    press_pos: Option<Vec2>,
+    dragging: bool,
+    recycle_armed_at: Option<Duration>,
+    active_touch: Option<u64>,
+    hovered_tableau: Option<usize>,
+    animations: Vec<CardAnimation>,
+    pub(crate) cascade: Option<WinCascade>,
+    events: Vec<GameEvent>,
+    foundation_flash: Option<(usize, f32)>,
+    // Whether the held cards would fit where they are, with the hand and cursor it was worked out for
+    drop_legal: Option<bool>,
+    drop_checked: (usize, PileRef, Vec2),
+    // Each column's length and shown count from before 'reveal_all', so they can be put back
+    #[cfg(feature = "cheats")]
+    pub(crate) shown_before_reveal: Option<Vec<(usize, u8)>>,
     mouse_pos: Vec2,
     previous_time: instant::Instant,
+    // Seconds between the last two updates, and its moving average
+    frame_time: f32,
+    average_frame_time: f32,
     tick: f32,
-    stream_handle: OutputStreamHandle
+    audio: Audio
+}
+
+const DEAL_CODE_DIGITS: &[u8; 36] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    InvalidCharacter(char),
+    TooLong,
+    InvalidCard(String)
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "deal code is empty"),
+            ParseError::InvalidCharacter(c) => write!(f, "'{}' isn't a valid deal code character", c),
+            ParseError::TooLong => write!(f, "deal code is too long"),
+            ParseError::InvalidCard(card) => write!(f, "'{}' isn't a card", card)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardLayout {
+    pub stock: Vec2,
+    pub talon: Vec2,
+    // Leftmost foundation and tableau, the rest follow to the right
+    pub foundation: Vec2,
+    pub tableau: Vec2,
+    // Gap between neighbouring foundations and tableaux
+    pub pile_spacing: f32,
+    pub card_size: Vec2,
+    // 'STACK_OFFSET' and the rest, grown or shrunk with the cards
+    pub stack_offset: f32,
+    pub talon_fan_offset: f32,
+    pub hover_fan_offset: f32,
+    pub pass_dot_size: f32,
+    pub pass_dot_spacing: f32
+}
+
+impl Default for BoardLayout {
+    fn default() -> Self {
+        Self {
+            stock: DECK_QUAD.pos,
+            talon: Vec2 { x: -520.0, y: 350.0 },
+            foundation: Vec2 { x: -160.0, y: 350.0 },
+            tableau: Vec2 { x: -700.0, y: 0.0 },
+            pile_spacing: 20.0,
+            card_size: CARD_SIZE,
+            stack_offset: STACK_OFFSET,
+            talon_fan_offset: TALON_FAN_OFFSET,
+            hover_fan_offset: HOVER_FAN_OFFSET,
+            pass_dot_size: PASS_DOT_SIZE,
+            pass_dot_spacing: PASS_DOT_SPACING
+        }
+    }
+}
+
+impl BoardLayout {
+    // The default layout stretched from 'SCREEN_SIZE' to a window of this many pixels.
+    // Cards keep their shape, scaled by whichever side grew least
+    pub fn for_viewport(width: i32, height: i32) -> Self {
+        let default = BoardLayout::default();
+        let scale = Vec2::new(width as f32 / SCREEN_SIZE.x as f32, height as f32 / SCREEN_SIZE.y as f32);
+        let card_scale = scale.x.min(scale.y);
+        let card_size = default.card_size * card_scale;
+        let scaled = |pos: Vec2| Vec2::new(pos.x * scale.x, pos.y * scale.y);
+        Self {
+            stock: scaled(default.stock),
+            talon: scaled(default.talon),
+            foundation: scaled(default.foundation),
+            tableau: scaled(default.tableau),
+            // Whatever keeps the columns as far apart as the rest of the board
+            pile_spacing: (default.card_size.x + default.pile_spacing) * scale.x - card_size.x,
+            card_size,
+            stack_offset: default.stack_offset * card_scale,
+            talon_fan_offset: default.talon_fan_offset * card_scale,
+            hover_fan_offset: default.hover_fan_offset * card_scale,
+            pass_dot_size: default.pass_dot_size * card_scale,
+            pass_dot_spacing: default.pass_dot_spacing * card_scale
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderConfig {
+    // Sprite sheet cell drawn for every face-down card
+    pub card_back_index: [u8; 2],
+    // Draws a dark border this many world units wide around every card, 'None' draws none
+    pub card_outline: Option<f32>,
+    pub foundation_hint: FoundationHint
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            card_back_index: [0, 4],
+            card_outline: None,
+            foundation_hint: FoundationHint::None
+        }
+    }
+}
+
+// What an empty foundation shows. The hints are expected on the last row of the
+// sprite sheet after the back and empty cells: the four suit symbols in card value
+// order (spades, hearts, clubs, diamonds) at columns 2 to 5, then the Ace outline at 6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoundationHint {
+    None,
+    // The suit the foundation is in the position of, spades first
+    SuitSymbol,
+    AceOutline
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PileRef {
+    Stock,
+    Talon,
+    Foundation(usize),
+    Tableau(usize)
 }
 
 #[derive(Debug, PartialEq)]
@@ -37,10 +258,14 @@ pub struct Tableau {
     pub cards: Vec<Card>,
     pub card_quads: Vec<Quad>,
     pub shown_cards: u8,
-    pub x_position: f32
+    pub x_position: f32,
+    pub y_position: f32,
+    pub card_size: Vec2,
+    // How far down each card sits from the one under it
+    pub stack_offset: f32
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Card {
     pub value: u8,
     pub rank: u8,
@@ -81,13 +306,38 @@ impl Card {
     }
 }
 
-#[derive(PartialEq, Debug)]
+const RANK_NAMES: &[u8; 13] = b"A23456789TJQK";
+const SUIT_NAMES: &[u8; 4] = b"shcd";
+
+// Written as rank then suit, "As", "Th", "Qc"
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", RANK_NAMES[self.rank as usize] as char, SUIT_NAMES[(self.value / 13) as usize] as char)
+    }
+}
+
+impl FromStr for Card {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidCard(s.to_string());
+        let bytes = s.as_bytes();
+        if bytes.len() != 2 {
+            return Err(invalid());
+        }
+        let rank = RANK_NAMES.iter().position(|c| *c == bytes[0].to_ascii_uppercase()).ok_or_else(invalid)?;
+        let suit = SUIT_NAMES.iter().position(|c| *c == bytes[1].to_ascii_lowercase()).ok_or_else(invalid)?;
+        Ok(Card::new((suit * 13 + rank) as u8))
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Color {
     Red,
     Black
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum Suit {
     Spade,
     Heart,
@@ -101,7 +351,10 @@ impl Tableau {
             cards: vec![],
             card_quads: vec![],
             shown_cards: 0,
-            x_position: 0.0
+            x_position: 0.0,
+            y_position: 0.0,
+            card_size: CARD_SIZE,
+            stack_offset: STACK_OFFSET
         }
     }
 
@@ -119,19 +372,19 @@ impl Tableau {
 
     pub fn calculate_card_quads(&mut self) {
         self.card_quads = vec![];
-        if self.cards.len() == 0 {
+        if self.cards.is_empty() {
             self.card_quads.push( 
                 Quad {
-                    pos: Vec2 { x: self.x_position, y: 0.0 },
-                    size: CARD_SIZE
+                    pos: Vec2 { x: self.x_position, y: self.y_position },
+                    size: self.card_size
                 }
             );
         } else {
             for i in 0..self.cards.len() {
                 self.card_quads.push( 
                     Quad {
-                        pos: Vec2 { x: self.x_position, y: -(i as f32 * 70.0) },
-                        size: CARD_SIZE
+                        pos: Vec2 { x: self.x_position, y: self.y_position - (i as f32 * self.stack_offset) },
+                        size: self.card_size
                     }
                 );
             }
@@ -139,7 +392,7 @@ impl Tableau {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Quad {
     pub pos: Vec2,
     pub size: Vec2
@@ -152,17 +405,26 @@ pub struct Stack {
 
 impl Stack {
     pub fn random_deck() -> Self {
-        let mut cards = vec![];
-        let mut possible_cards : Vec<u8> = (0..52).collect();
-        
-        for _ in 0..52 {
-            let rand_index = rand::thread_rng().gen_range(0..possible_cards.len());
-            let random_card = possible_cards.remove(rand_index);
-            cards.push(Card::new(random_card));
+        Stack::deck_with_rng(&mut rand::thread_rng())
+    }
+
+    // ChaCha's output is fixed by its spec, so a seed deals the same cards on every platform
+    pub fn seeded_deck(seed: u64) -> Self {
+        Stack::deck_with_rng(&mut ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    // Any source of randomness, including a '&mut dyn RngCore'
+    pub fn deck_with_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut values: [u8; 52] = std::array::from_fn(|i| i as u8);
+
+        // Fisher-Yates, each card swaps with one at or below it
+        for i in (1..values.len()).rev() {
+            let j = rng.gen_range(0..=i);
+            values.swap(i, j);
         }
 
         Stack {
-            cards,
+            cards: values.iter().map(|value| Card::new(*value)).collect(),
             quad: DECK_QUAD,
         }
     }
@@ -184,7 +446,7 @@ impl Quad {
     }
 
     pub fn contains(&self, pos: Vec2) -> bool {
-        return pos.y >= self.bottom() && 
+        pos.y >= self.bottom() && 
                pos.y <= self.top() && 
                pos.x >= self.left() && 
                pos.x <= self.right()
@@ -222,7 +484,23 @@ impl Mul<f32> for Vec2 {
     type Output = Vec2;
 
     fn mul(self, mul: f32) -> Vec2 {
-        Vec2::new(self.x as f32 * mul, self.y as f32 * mul)
+        Vec2::new(self.x * mul, self.y * mul)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Self) -> Vec2 {
+        Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Self) -> Vec2 {
+        Vec2::new(self.x - rhs.x, self.y - rhs.y)
     }
 }
 
@@ -251,6 +529,10 @@ impl Vec2 {
         Vec2::new(0,0)
     }
 
+    pub fn length(&self) -> f32 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
     pub fn normalize(&mut self) -> Self {
         let mag = (self.x * self.x + self.y * self.y).sqrt();
         if mag != 0.0 { 
@@ -262,49 +544,223 @@ impl Vec2 {
 }
 
 impl GameState {
-    pub fn new(stream_handle: OutputStreamHandle) -> Self {
+    pub fn new(audio: Audio) -> Self {
+        GameState::new_with_rules(audio, Rules::default())
+    }
+
+    pub fn new_with_rules(audio: Audio, rules: Rules) -> Self {
+        GameState::new_with_seed(audio, rules, rand::thread_rng().gen())
+    }
+
+    pub fn new_with_seed(audio: Audio, rules: Rules, seed: u64) -> Self {
+        GameState::new_with_layout(audio, rules, seed, SCREEN_SIZE)
+    }
+
+    // For a window of some other size than 'SCREEN_SIZE', every pile is placed in proportion
+    pub fn new_with_viewport(audio: Audio, width: i32, height: i32) -> Self {
+        GameState::new_with_layout(audio, Rules::default(), rand::thread_rng().gen(), Vec2i { x: width, y: height })
+    }
 
-        let mut stock = Stack::random_deck();
+    fn new_with_layout(audio: Audio, rules: Rules, seed: u64, viewport: Vec2i) -> Self {
 
-        let tableaux = GameState::fill_tableaux(&mut stock);
+        let mut stock = Stack::seeded_deck(seed);
+
+        let layout = BoardLayout::for_viewport(viewport.x, viewport.y);
+
+        stock.quad.pos = layout.stock;
+        stock.quad.size = layout.card_size;
+
+        let tableaux = GameState::fill_tableaux(&mut stock, &layout, rules.dealt_face_up());
 
         let mut talon = Stack::empty();
-        talon.quad.pos = Vec2::new(-520, 350);
+        talon.quad.pos = layout.talon;
+        talon.quad.size = layout.card_size;
+
+        let mut hand = Stack::empty();
+        hand.quad.size = layout.card_size;
 
-        GameState {
+        let mut state = GameState {
             stock,
             talon,
             tableaux,
-            hand: Stack::empty(),
-            foundations: GameState::create_foundations(),
+            hand,
+            foundations: GameState::create_foundations(&layout, rules.variant.foundation_count()),
+            layout,
+            viewport,
+            seed,
+            // The first pass through the stock doesn't need a recycle
+            stock_passes_remaining: rules.stock_passes.map(|passes| passes.saturating_sub(1)),
+            rules,
+            settings: Settings::default(),
+            render_config: RenderConfig::default(),
+            key_bindings: KeyBindings::default(),
+            talon_shown: 0,
             previous_time: instant::Instant::now(),
+            frame_time: 0.0,
+            average_frame_time: 0.0,
             mouse_pos: Vec2::zero(),
-            hand_origin: 0,
+            spring_stiffness: 900.0,
+            damping: 50.0,
+            hand_origin: PileRef::Talon,
+            history: vec![],
+            game_time: Duration::ZERO,
+            score: 0,
+            vegas_balance: 0,
+            winning_move: None,
+            hint_cycle: None,
+            autosave_pending: false,
+            autosaved_at: None,
+            recent_reveals: vec![],
+            mistakes: 0,
+            talon_shown_before: 0,
+            demo_enabled: false,
+            double_click_ms: 300,
+            idle_time: 0.0,
+            demo_played_at: 0.0,
+            demo_stock_moves: 0,
+            demo_rng: ChaCha8Rng::seed_from_u64(seed),
+            sound_rng: ChaCha8Rng::seed_from_u64(seed),
+            hand_velocity: Vec2::zero(),
+            paused: false,
+            last_click: None,
+            press_pos: None,
+            dragging: false,
+            recycle_armed_at: None,
+            active_touch: None,
+            hovered_tableau: None,
+            animations: vec![],
+            cascade: None,
+            events: vec![],
+            foundation_flash: None,
+            drop_legal: None,
+            drop_checked: (0, PileRef::Talon, Vec2::zero()),
+            #[cfg(feature = "cheats")]
+            shown_before_reveal: None,
             tick: 0.0,
-            stream_handle
+            audio
+        };
+
+        if state.rules.autoplay_aces {
+            state.play_dealt_aces();
+        }
+        state
+    }
+
+    // Deals another game with the same rules in the same window, keeping the player's preferences.
+    // Under VegasCumulative whatever this game won or lost is banked into the next one's balance
+    pub fn new_game(&mut self) {
+        let audio = std::mem::replace(&mut self.audio, Audio::silent());
+        let mut next = GameState::new_with_layout(audio, self.rules.clone(), rand::thread_rng().gen(), self.viewport);
+        next.settings = self.settings.clone();
+        next.key_bindings = self.key_bindings.clone();
+        next.render_config = self.render_config.clone();
+        next.double_click_ms = self.double_click_ms;
+        if self.rules.scoring == ScoringMode::VegasCumulative {
+            next.vegas_balance = self.score();
+        }
+        *self = next;
+    }
+
+    // Part of the deal rather than moves, so nothing is recorded or scored.
+    // Keeps going while uncovering a card turns up another Ace
+    fn play_dealt_aces(&mut self) {
+        loop {
+            let ace = self.tableaux.iter().enumerate().find_map(|(t, tableau)| {
+                let card = tableau.cards.last().filter(|card| card.rank == 0)?;
+                self.target_foundation(card).map(|f| (t, f))
+            });
+            let Some((t, f)) = ace else { return };
+
+            let run = self.tableaux[t].pop_run(1);
+            self.foundations[f].push(run);
+            self.ensure_top_revealed();
+        }
+    }
+
+    // Every quad, for hit testing and drawing, takes its size from here
+    pub fn card_size(&self) -> Vec2 {
+        self.layout.card_size
+    }
+
+    fn play_place_sound(&mut self) {
+        let variation = self.settings.pitch_variation;
+        let speed = if variation > 0.0 { 1.0 + self.sound_rng.gen_range(-variation..=variation) } else { 1.0 };
+        self.audio.play_at_speed(Sound::Place, speed);
+    }
+
+    pub fn shutdown_audio(&mut self) {
+        self.audio.shutdown();
+    }
+
+    // Whatever 'rules.scoring' counts, Standard points are kept as moves are made
+    // while Vegas only depends on how many cards are up
+    pub fn score(&self) -> i32 {
+        let vegas = self.foundation_progress() as i32 * VEGAS_CARD_PAYOUT - VEGAS_BUY_IN;
+        match self.rules.scoring {
+            ScoringMode::None => 0,
+            ScoringMode::Standard => self.score,
+            ScoringMode::Vegas => vegas,
+            ScoringMode::VegasCumulative => self.vegas_balance + vegas
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn deal_code(&self) -> String {
+        let mut seed = self.seed;
+        let mut code = vec![];
+        loop {
+            code.push(DEAL_CODE_DIGITS[(seed % 36) as usize]);
+            seed /= 36;
+            if seed == 0 { break; }
+        }
+        code.reverse();
+        String::from_utf8(code).unwrap()
+    }
+
+    pub fn from_deal_code(code: &str, audio: Audio) -> Result<Self, ParseError> {
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        let mut seed: u64 = 0;
+        for c in code.chars() {
+            let digit = c.to_digit(36).ok_or(ParseError::InvalidCharacter(c))?;
+            seed = seed.checked_mul(36)
+                .and_then(|seed| seed.checked_add(digit as u64))
+                .ok_or(ParseError::TooLong)?;
         }
+
+        Ok(GameState::new_with_seed(audio, Rules::default(), seed))
     }
 
-    pub fn fill_tableaux(deck: &mut Stack) -> [Tableau; 7] {
+    pub fn fill_tableaux(deck: &mut Stack, layout: &BoardLayout, initial_shown: u8) -> [Tableau; 7] {
         let mut tableau = Tableau::empty_tableaux();
-        for i in 0..7 {
-            let x_position = -700.0 + ((CARD_SIZE.x + 20.0) * i as f32);
+        for (i, slot) in tableau.iter_mut().enumerate() {
+            let x_position = layout.tableau.x + ((layout.card_size.x + layout.pile_spacing) * i as f32);
             let mut stack = Tableau {
                 x_position,
+                y_position: layout.tableau.y,
+                card_size: layout.card_size,
+                stack_offset: layout.stack_offset,
                 card_quads: vec![],
                 cards: deck.cards.drain(0..(i + 1)).collect(),
-                shown_cards: 1
+                shown_cards: initial_shown.clamp(1, i as u8 + 1)
             };
             stack.calculate_card_quads();
-            tableau[i] = stack;
+            *slot = stack;
         }
         tableau
     }
 
-    pub fn create_foundations() -> [Stack; 4] {
-        let mut foundations = [Stack::empty(), Stack::empty(), Stack::empty(), Stack::empty()];
-        for i in 0..4 {
-            foundations[i].quad.pos =  Vec2::new(-160.0 + ((CARD_SIZE.x + 20.0) * i as f32), 350.0);
+    pub fn create_foundations(layout: &BoardLayout, count: usize) -> Vec<Stack> {
+        let mut foundations: Vec<Stack> = (0..count).map(|_| Stack::empty()).collect();
+        for (i, foundation) in foundations.iter_mut().enumerate() {
+            foundation.quad.pos =  Vec2::new(layout.foundation.x + ((layout.card_size.x + layout.pile_spacing) * i as f32), layout.foundation.y);
+            foundation.quad.size = layout.card_size;
         } 
         foundations
     }
@@ -314,190 +770,1643 @@ impl GameState {
         let elapsed_time = current_time.duration_since(self.previous_time).as_secs_f32();
         self.previous_time = current_time;
 
-        self.tick += elapsed_time;
+        self.frame_time = elapsed_time;
+        self.average_frame_time = if self.average_frame_time == 0.0 {
+            elapsed_time
+        } else {
+            self.average_frame_time + (elapsed_time - self.average_frame_time) * FPS_SMOOTHING
+        };
 
-        if self.tick > TICK_TIME {
-            self.hand.quad.pos = self.mouse_pos;
+        if self.paused { return; }
+        self.tick_fixed(elapsed_time);
+    }
+
+    pub fn last_frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    pub fn fps(&self) -> f32 {
+        if self.average_frame_time > 0.0 { 1.0 / self.average_frame_time } else { 0.0 }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    // The time spent paused is skipped rather than caught up on the next update
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.previous_time = instant::Instant::now();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Advances the game by 'dt' seconds without looking at the clock,
+    // so simulations and tests can step the game deterministically
+    pub fn tick_fixed(&mut self, dt: f32) {
+        self.game_time += Duration::from_secs_f32(dt);
+        self.idle_time += dt;
+
+        self.tick += dt;
+
+        while self.tick > TICK_TIME {
+            self.update_hand(TICK_TIME);
+            self.update_animations(TICK_TIME);
+            self.update_cascade(TICK_TIME);
+            self.update_flash(TICK_TIME);
             self.tick -= TICK_TIME;
         }
+
+        self.update_drop_legal();
+        self.update_autosave();
+
+        let (game_time, delay) = (self.game_time, self.settings.reveal_delay);
+        self.recent_reveals.retain(|(_, at)| game_time - *at < delay);
+    }
+
+    // Only redone when the hand or the cursor has changed since the last check
+    fn update_drop_legal(&mut self) {
+        let checked = (self.hand.cards.len(), self.hand_origin, self.mouse_pos);
+        if checked == self.drop_checked { return; }
+        self.drop_checked = checked;
+
+        self.drop_legal = match self.drop_target() {
+            Some(pile) if !self.hand.cards.is_empty() => Some(self.can_drop_on(pile)),
+            _ => None
+        };
+    }
+
+    // None when the held cards aren't over any pile, or nothing is held
+    pub fn hand_drop_legal(&self) -> Option<bool> {
+        self.drop_legal
+    }
+
+    // The pile a click would drop the held cards on, hit tested the same way 'click' does
+    fn drop_target(&self) -> Option<PileRef> {
+        let tableau = self.tableaux.iter()
+            .position(|tableau| tableau.card_quads[tableau.card_quads.len() - 1].contains(self.mouse_pos));
+        if let Some(t) = tableau {
+            return Some(PileRef::Tableau(t));
+        }
+        self.foundations.iter()
+            .position(|foundation| foundation.quad.contains(self.mouse_pos))
+            .map(PileRef::Foundation)
+    }
+
+    fn update_hand(&mut self, dt: f32) {
+        if self.hand.cards.is_empty() || !self.settings.animations_enabled {
+            self.hand.quad.pos = self.mouse_pos;
+            self.hand_velocity = Vec2::zero();
+            return;
+        }
+
+        // Damped spring pulling the held cards toward the cursor,
+        // a damping below critical (2 * sqrt(stiffness)) gives a slight overshoot
+        let displacement = self.mouse_pos - self.hand.quad.pos;
+        let acceleration = displacement * self.spring_stiffness - self.hand_velocity * self.damping;
+        self.hand_velocity += acceleration * dt;
+        self.hand.quad.pos += self.hand_velocity * dt;
+
+        if self.hand_velocity.length() < HAND_SETTLE_EPSILON && displacement.length() < HAND_SETTLE_EPSILON {
+            self.hand.quad.pos = self.mouse_pos;
+            self.hand_velocity = Vec2::zero();
+        }
+    }
+
+    fn update_animations(&mut self, dt: f32) {
+        for animation in self.animations.iter_mut() {
+            animation.elapsed += dt;
+        }
+        self.animations.retain(|animation| !animation.finished());
+    }
+
+    fn update_cascade(&mut self, dt: f32) {
+        if let Some(cascade) = &mut self.cascade {
+            cascade.update(dt);
+            if cascade.finished() {
+                self.cascade = None;
+            }
+        }
+    }
+
+    pub fn cascade_active(&self) -> bool {
+        self.cascade.is_some()
+    }
+
+    fn update_flash(&mut self, dt: f32) {
+        if let Some((f, remaining)) = self.foundation_flash {
+            self.foundation_flash = if remaining > dt { Some((f, remaining - dt)) } else { None };
+        }
+    }
+
+    pub fn foundation_flash(&self) -> Option<usize> {
+        self.foundation_flash.map(|(f, _)| f)
+    }
+
+    pub(crate) fn emit(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Slides cards from where they were held to wherever they were just placed
+    pub(crate) fn snap_cards(&mut self, values: &[u8], from: Vec2) {
+        if !self.settings.animations_enabled { return; }
+        for (i, value) in values.iter().enumerate() {
+            self.animations.retain(|animation| animation.value != *value);
+            let card_from = Vec2::new(from.x, from.y - (i as f32 * self.layout.stack_offset));
+            self.animations.push(CardAnimation::new(*value, card_from, SNAP_TIME));
+        }
+    }
+
+    pub fn is_animating(&self, card: &Card) -> bool {
+        self.animations.iter().any(|animation| animation.value == card.value)
+    }
+
+    pub fn animated_pos(&self, card: &Card, pos: Vec2) -> Vec2 {
+        match self.animations.iter().find(|animation| animation.value == card.value) {
+            Some(animation) => animation.position(pos),
+            None => pos
+        }
+    }
+
+    fn held_values(&self) -> Vec<u8> {
+        self.hand.cards.iter().map(|card| card.value).collect()
     }
 
     pub fn mouse_click(&mut self) {
-        if self.hand.cards.len() == 0 {
+        self.click();
+        self.ensure_top_revealed();
+    }
+
+    fn click(&mut self) {
+        if self.hand.cards.is_empty() {
+            self.talon_shown_before = self.talon_shown;
             if self.stock.quad.contains(self.mouse_pos) {
-                if self.stock.cards.len() > 0 {
-                    self.talon.cards.insert(0, self.stock.cards.pop().unwrap());
-                } else {
-                    self.stock.cards.splice(.., self.talon.cards.drain(..));
+                if !self.stock.cards.is_empty() {
+                    if Move::FlipStock.apply(self).is_err() { return; }
+                    self.play_place_sound();
+                    if self.rules.autoplay_on_flip {
+                        self.play_talon_to_foundation();
+                    }
+                } else if self.can_recycle() {
+                    if self.rules.require_recycle_confirm && self.stock_passes_remaining == Some(1) && !self.recycle_armed() {
+                        self.recycle_armed_at = Some(self.game_time);
+                    } else if Move::RecycleTalon.apply(self).is_ok() {
+                        self.play_place_sound();
+                    }
                 }
-                self.play_audio(1);
             }
-            if self.talon.quad.contains(self.mouse_pos) {
-                if self.talon.cards.len() > 0 {
-                    self.hand.cards.push(self.talon.cards.remove(0));
-                    self.hand_origin = 0;
-                    self.play_audio(0);
-                    return;
-                }
+            if self.talon_top_quad().contains(self.mouse_pos) && !self.talon.cards.is_empty() {
+                let card = self.take_talon_top();
+                self.hand.cards.push(card);
+                self.hand_origin = PileRef::Talon;
+                self.audio.play(Sound::PickUp);
+                return;
             }
-            for (t, tableau) in self.tableaux.iter_mut().enumerate() {
+            let max_movable = self.max_movable();
+            for t in 0..self.tableaux.len() {
+                let tableau = &self.tableaux[t];
                 // Reverse is important, checks collision front to back
                 for i in (0..tableau.card_quads.len()).rev() {
-                    if tableau.cards.len() > 0 {
-                        if i >= tableau.cards.len() - tableau.shown_cards as usize {
-                            // for each shown card in each tableau
-                            if tableau.card_quads[i].contains(self.mouse_pos) {
-                                tableau.shown_cards -= (tableau.cards.len() - i) as u8;
-                                self.hand.cards.splice(.., tableau.cards.drain(i..tableau.cards.len()));
-                                tableau.calculate_card_quads();
-                                self.hand_origin = 5 + t as u8;
-                                self.play_audio(0);
+                    if !tableau.cards.is_empty() && i >= tableau.cards.len() - tableau.shown_cards as usize {
+                        // for each shown card in each tableau
+                        if tableau.card_quads[i].contains(self.mouse_pos) {
+                            if tableau.cards.len() - i > max_movable || !self.is_valid_run(&tableau.cards[i..]) {
+                                return;
+                            }
+                            if self.is_settling(&tableau.cards[i]) {
                                 return;
                             }
+                            let tableau = &mut self.tableaux[t];
+                            self.hand.cards = tableau.pop_run(tableau.cards.len() - i);
+                            self.hand_origin = PileRef::Tableau(t);
+                            self.audio.play(Sound::PickUp);
+                            return;
                         }
                     }
 
                 }
             }
             for (f, foundation) in self.foundations.iter_mut().enumerate() {
-                if foundation.cards.len() > 0 {
-                    if foundation.quad.contains(self.mouse_pos) {
-                        self.hand.cards.push(foundation.cards.remove(0));
-                        self.hand_origin = 1 + f as u8;
-                        self.play_audio(0);
-                        return;
-                    }
+                if !foundation.cards.is_empty() && foundation.quad.contains(self.mouse_pos) {
+                    self.hand.cards = foundation.pop_run(1);
+                    self.hand_origin = PileRef::Foundation(f);
+                    self.audio.play(Sound::PickUp);
+                    return;
                 }
             }
         } else {
-            for (t, tableau) in self.tableaux.iter_mut().enumerate() {
+            let held = self.held_values();
+            let hand_pos = self.hand.quad.pos;
+            for t in 0..self.tableaux.len() {
+                let tableau = &self.tableaux[t];
                 if tableau.card_quads[tableau.card_quads.len() - 1].contains(self.mouse_pos) {
-                    if tableau.cards.len() == 0 || 
-                        GameState::can_place_on_tableau(&tableau.cards[tableau.cards.len() - 1], &self.hand.cards[0]) {
-                            tableau.shown_cards += self.hand.cards.len() as u8;
-                            tableau.cards.append(&mut self.hand.cards);
-                            tableau.calculate_card_quads();
-                            match self.hand_origin {
-                                5.. => {
-                                    let origin = self.hand_origin - 5;
-                                    if t as u8 != origin {
-                                        if self.tableaux[origin as usize].cards.len() > 0 {
-                                            if self.tableaux[origin as usize].shown_cards == 0 {
-                                                self.tableaux[origin as usize].shown_cards += 1;
-                                            }
-                                        } 
-                                    }
-                                },
-                                _ => {}
-                            }
-                        self.play_audio(1);
+                    // Putting cards back where they came from only cancels the pick up,
+                    // the column they left is shorter so it can't be checked like a move
+                    if self.hand_origin == PileRef::Tableau(t) {
+                        self.return_card();
+                        return;
+                    }
+                    if self.tableau_accepts(tableau, &self.hand.cards[0]) {
+                        if let Ok(Some(mv)) = self.hand_move(PileRef::Tableau(t)) {
+                            self.play_hand_move(mv);
+                        }
+                        self.snap_cards(&held, hand_pos);
+                        self.play_place_sound();
                         return;
                     }
                 }
             }
-            for foundation in self.foundations.iter_mut() {
-                if foundation.quad.contains(self.mouse_pos) {
-                    if self.hand.cards.len() == 1 {
-                        if GameState::can_place_on_foundation(&foundation, &self.hand.cards[0]) {
-                                foundation.cards.insert(0, self.hand.cards.remove(0));
-                                match self.hand_origin {
-                                    5.. => {
-                                        let origin = self.hand_origin - 5;
-                                        if self.tableaux[origin as usize].cards.len() > 0 {
-                                            if self.tableaux[origin as usize].shown_cards == 0 {
-                                                self.tableaux[origin as usize].shown_cards += 1;
-                                            }
-                                        } 
-                                    },
-                                    _ => {}
-                                }
-                                self.play_audio(1);
-                                return;
-                            }
+            for f in 0..self.foundations.len() {
+                if self.foundations[f].quad.contains(self.mouse_pos) {
+                    // Back where it came from is a cancel, so nothing is scored either way
+                    if self.hand_origin == PileRef::Foundation(f) {
+                        self.return_card();
+                        return;
+                    }
+                    if let Err(MoveError::RunToFoundation) = self.hand_move(PileRef::Foundation(f)) {
+                        self.emit(GameEvent::InvalidMove { reason: InvalidReason::RunOnFoundation });
+                        self.foundation_flash = Some((f, FLASH_TIME));
+                        self.audio.play(Sound::Invalid);
+                        self.mistakes += 1;
+                        return;
+                    }
+                    // Shuffling a card between foundations isn't a move, undo couldn't follow it
+                    let from_foundation = matches!(self.hand_origin, PileRef::Foundation(_));
+                    if !from_foundation && self.can_place_on_foundation(&self.foundations[f], &self.hand.cards[0]) {
+                        if let Ok(Some(mv)) = self.hand_move(PileRef::Foundation(f)) {
+                            self.play_hand_move(mv);
+                        }
+                        self.snap_cards(&held, hand_pos);
+                        self.play_place_sound();
+                        return;
                     }
                 }
             }
+            // Nothing took the cards, a mistake if they were over some other pile
+            if let Some(pile) = self.drop_target() {
+                if pile != self.hand_origin {
+                    self.mistakes += 1;
+                }
+            }
         }
     }
 
-    pub fn return_card(&mut self) {
-        if self.hand.cards.len() != 0 {
-            match self.hand_origin {
-                0 => {
-                    self.talon.cards.insert(0, self.hand.cards.remove(0));
-                },
-                1..=4 => {
-                    self.foundations[(self.hand_origin - 1) as usize].cards.insert(0, self.hand.cards.remove(0));
-                },
-                5.. => {
-                    self.tableaux[(self.hand_origin - 5) as usize].shown_cards += self.hand.cards.len() as u8;
-                    self.tableaux[(self.hand_origin - 5) as usize].cards.append(&mut self.hand.cards);
-                    self.tableaux[(self.hand_origin - 5) as usize].calculate_card_quads();
-                }
-            }
-            self.play_audio(1);
+    // A drop is played like any other move: the held cards go back where they were
+    // picked up, talon fan included, and 'mv' takes them from there. If it isn't legal
+    // after all they simply stay put
+    fn play_hand_move(&mut self, mv: Move) {
+        let origin = self.hand_origin;
+        let run = std::mem::take(&mut self.hand.cards);
+        self.pile_mut(origin).push(run);
+        if origin == PileRef::Talon {
+            self.talon_shown = self.talon_shown_before;
+        }
+        if let Err(e) = mv.apply(self) {
+            log::warn!("Dropped cards couldn't be played: {}", e);
         }
     }
 
-    fn can_place_on_tableau(tableau: &Card, hand: &Card) -> bool {
-        tableau.color != hand.color && tableau.rank == hand.rank + 1
+    // Drops onto a pile that wouldn't take the cards
+    pub fn mistakes(&self) -> u32 {
+        self.mistakes
     }
 
-    fn can_place_on_foundation(foundation: &Stack, hand: &Card) -> bool {
-        let foundation_size = foundation.cards.len();
-        if foundation_size == 0 {
-            if hand.rank == 0 { return true; }
-            else { return false; }
+    pub fn recycle_armed(&self) -> bool {
+        match self.recycle_armed_at {
+            Some(armed_at) => (self.game_time - armed_at).as_secs_f32() < RECYCLE_CONFIRM_TIME,
+            None => false
         }
-        let foundation_card = &foundation.cards[0];
-        if foundation_card.suit == hand.suit && foundation_card.rank == hand.rank - 1 { return true }
-        false
     }
 
-    fn play_audio(&self, id: u8) {
-        let sink = Sink::try_new(&self.stream_handle).unwrap();
-       
-        let audio;
+    // Passes through the stock still to come counting the current one, 'None' when unlimited
+    pub fn passes_left(&self) -> Option<u32> {
+        if !self.rules.recycle_allowed {
+            return Some(1);
+        }
+        self.stock_passes_remaining.map(|recycles| recycles + 1)
+    }
+
+    pub(crate) fn can_recycle(&self) -> bool {
+        self.rules.recycle_allowed
+            && self.stock.cards.is_empty() && !self.talon.cards.is_empty() && self.stock_passes_remaining != Some(0)
+    }
+
+    // Whether clicking the stock does anything, a draw or a recycle
+    pub fn can_flip_stock(&self) -> bool {
+        !self.stock.cards.is_empty() || self.can_recycle()
+    }
 
-        match id {
-            0 => { audio = include_bytes!("aud/pick_up_card.ogg").to_vec() },
-            _ => { audio = include_bytes!("aud/place_card.ogg").to_vec() }
+    pub(crate) fn flip_stock(&mut self) {
+        let count = self.rules.draw_count.min(self.stock.cards.len());
+        for _ in 0..count {
+            self.talon.cards.insert(0, self.stock.cards.pop().unwrap());
         }
-        
-        let source = Decoder::new(Cursor::new(audio)).unwrap();
-        sink.append(source);
-        sink.play();
-        sink.detach();
+        self.talon_shown = count;
     }
 
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::MouseInput { 
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                ..
-            } => {
-                self.mouse_click();
-                return true;
-            }
-            WindowEvent::MouseInput { 
-                state: ElementState::Pressed,
-                button: MouseButton::Right,
-                ..
-            } => {
-                self.return_card();
-                return true;
-            }
-            WindowEvent::CursorMoved {
-                position,
-                ..
-            } => {
-                self.mouse_pos = Vec2::new((position.x - (SCREEN_SIZE.x as f32 / 2.0) as f64) * 2.0, -(position.y - (SCREEN_SIZE.y as f32 / 2.0) as f64) * 2.0);
-                return true;
-            }
-            _ => { 
-                return false;
-            }
+    pub(crate) fn take_talon_top(&mut self) -> Card {
+        let card = self.talon.cards.remove(0);
+        // Once a whole draw is played the top of the previous one shows
+        self.talon_shown = self.talon_shown.saturating_sub(1).max(1).min(self.talon.cards.len());
+        card
+    }
+
+    pub fn talon_top_quad(&self) -> Quad {
+        let fanned = self.talon_shown.max(1) - 1;
+        Quad {
+            pos: Vec2::new(self.talon.quad.pos.x + fanned as f32 * self.layout.talon_fan_offset, self.talon.quad.pos.y),
+            size: self.talon.quad.size
         }
     }
+
+    // Smallest quad around every pile, columns included however far they've grown.
+    // Empty piles still have their quad, so this always covers the dealt layout
+    pub fn board_bounds(&self) -> Quad {
+        let mut quads = vec![self.stock.quad, self.talon.quad, self.talon_top_quad()];
+        quads.extend(self.foundations.iter().map(|foundation| foundation.quad));
+        for tableau in &self.tableaux {
+            quads.extend(&tableau.card_quads);
+        }
+
+        let (mut left, mut right, mut bottom, mut top) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+        for quad in quads {
+            left = left.min(quad.left());
+            right = right.max(quad.right());
+            bottom = bottom.min(quad.bottom());
+            top = top.max(quad.top());
+        }
+        Quad {
+            pos: Vec2::new((left + right) / 2.0, (bottom + top) / 2.0),
+            size: Vec2::new(right - left, top - bottom)
+        }
+    }
+
+    pub(crate) fn recycle_talon(&mut self) {
+        // The splice would throw away anything still in the stock
+        if !self.stock.cards.is_empty() { return; }
+        let cards = self.talon.cards.len();
+        // Talon top goes to the bottom of the stock, so the oldest draw comes off first again
+        self.stock.cards.splice(.., self.talon.cards.drain(..));
+        self.emit(GameEvent::StockRecycled { cards });
+        self.talon_shown = 0;
+        if let Some(passes) = &mut self.stock_passes_remaining {
+            *passes -= 1;
+        }
+        self.recycle_armed_at = None;
+    }
+
+    // Only to a foundation, as its own move so it can be undone apart from the flip
+    fn play_talon_to_foundation(&mut self) {
+        let Some(card) = self.talon.cards.first() else { return };
+        let Some(to) = self.target_foundation(card) else { return };
+
+        let mv = Move::TalonToFoundation { to };
+        let (values, from) = self.moved_cards(&mv);
+        if self.apply_move(&mv).is_ok() {
+            self.snap_cards(&values, from);
+        }
+    }
+
+    // Sends the talon top up if it can go, otherwise onto the tableau it fits best
+    pub fn play_talon_top(&mut self) -> bool {
+        if !self.hand.cards.is_empty() || self.talon.cards.is_empty() { return false; }
+
+        let Some(mv) = self.suggested_move_for(PileRef::Talon, 0) else {
+            self.audio.play(Sound::Invalid);
+            return false;
+        };
+        let (values, from) = self.moved_cards(&mv);
+        if self.apply_move(&mv).is_err() { return false; }
+        self.snap_cards(&values, from);
+        self.play_place_sound();
+        true
+    }
+
+    // Brings the next card of the fanned draw to the top, sending the top under the rest of the draw.
+    // Only the shown cards rotate, the stock and older draws are untouched
+    pub fn cycle_talon(&mut self) {
+        let shown = self.talon_shown.min(self.talon.cards.len());
+        if !self.hand.cards.is_empty() || shown < 2 { return; }
+        self.talon.cards[..shown].rotate_left(1);
+    }
+
+    pub fn return_card(&mut self) {
+        if !self.hand.cards.is_empty() {
+            let held = self.held_values();
+            self.snap_cards(&held, self.hand.quad.pos);
+            let origin = self.hand_origin;
+            let run = std::mem::take(&mut self.hand.cards);
+            self.pile_mut(origin).push(run);
+            self.autosave_pending = true;
+            // Put back the fan as it was when the card was picked up
+            if origin == PileRef::Talon {
+                self.talon_shown = self.talon_shown_before;
+            }
+            self.play_place_sound();
+        }
+        self.ensure_top_revealed();
+    }
+
+    // 'return_card' for when the hand can't be trusted, an origin that doesn't exist or
+    // couldn't hold the cards puts them on the first column instead. Always empties the hand
+    pub fn clear_hand(&mut self) {
+        let valid = match self.hand_origin {
+            PileRef::Tableau(t) => t < self.tableaux.len(),
+            // Cards only ever come off the talon and foundations one at a time
+            PileRef::Foundation(f) => f < self.foundations.len() && self.hand.cards.len() == 1,
+            _ => self.hand.cards.len() == 1
+        };
+        if !valid {
+            log::warn!("Held cards came from nowhere they can go back to, putting them on the first column");
+            self.hand_origin = PileRef::Tableau(0);
+        }
+        self.return_card();
+    }
+
+    // A column with face-down cards but nothing shown can never be clicked again,
+    // so flip its top card. Nothing should leave a column like this, but it's cheap to guard.
+    // The column held cards were lifted from is skipped, they may still be put back
+    pub fn ensure_top_revealed(&mut self) {
+        let held_from = match self.hand_origin {
+            PileRef::Tableau(t) if !self.hand.cards.is_empty() => Some(t),
+            _ => None
+        };
+        for t in 0..self.tableaux.len() {
+            if held_from != Some(t) {
+                self.reveal_top(t);
+            }
+        }
+    }
+
+    // Turns over the top of a column when nothing on it is face up, returning whether it did
+    pub(crate) fn reveal_top(&mut self, t: usize) -> bool {
+        let tableau = &mut self.tableaux[t];
+        if tableau.cards.is_empty() || tableau.shown_cards > 0 {
+            return false;
+        }
+        tableau.shown_cards = 1;
+        tableau.calculate_card_quads();
+        let value = tableau.cards[tableau.cards.len() - 1].value;
+        if self.settings.reveal_delay > Duration::ZERO {
+            self.recent_reveals.push((value, self.game_time));
+        }
+        self.emit(GameEvent::CardRevealed { value });
+        self.audio.play(Sound::Flip);
+        true
+    }
+
+    // For a new position, everything timed against the old game's clock goes with it
+    pub(crate) fn reset_clock(&mut self) {
+        self.game_time = Duration::ZERO;
+        self.recent_reveals.clear();
+        self.last_click = None;
+        self.recycle_armed_at = None;
+        self.autosaved_at = None;
+    }
+
+    // Cards still inside 'reveal_delay' can't be picked up
+    fn is_settling(&self, card: &Card) -> bool {
+        self.recent_reveals.iter().any(|(value, _)| *value == card.value)
+    }
+
+    // World space center of a pile, or of one of its cards for tableaux.
+    // With no index (or one out of range) a tableau gives its top card, which is where drops land
+    pub fn center_of(&self, pile: PileRef, card_index: Option<usize>) -> Vec2 {
+        match pile {
+            PileRef::Stock => self.stock.quad.pos,
+            PileRef::Talon => self.talon_top_quad().pos,
+            PileRef::Foundation(f) => self.foundations[f].quad.pos,
+            PileRef::Tableau(t) => {
+                let quads = &self.tableaux[t].card_quads;
+                let i = card_index.unwrap_or(quads.len() - 1).min(quads.len() - 1);
+                quads[i].pos
+            }
+        }
+    }
+
+    pub fn hovered_tableau(&self) -> Option<usize> {
+        self.hovered_tableau
+    }
+
+    pub fn talon_top(&self) -> Option<&Card> {
+        self.talon.cards.first()
+    }
+
+    pub fn stock_is_empty(&self) -> bool {
+        self.stock.cards.is_empty()
+    }
+
+    pub fn max_movable(&self) -> usize {
+        match self.rules.variant {
+            GameVariant::Klondike | GameVariant::Thoughtful => usize::MAX,
+            GameVariant::FreeCell { free_cells } => {
+                // Each empty column doubles what can be moved through the free cells
+                let empty_tableaux = self.tableaux.iter()
+                    .filter(|tableau| tableau.cards.is_empty())
+                    .count();
+                (free_cells + 1).checked_shl(empty_tableaux as u32).unwrap_or(usize::MAX)
+            }
+        }
+    }
+
+    // Foundations keep their top card at index 0
+    pub fn foundation_summary(&self) -> Vec<(Option<Suit>, Option<u8>)> {
+        self.foundations.iter()
+            .map(|foundation| match foundation.cards.first() {
+                Some(top) => (Some(top.suit), Some(top.rank)),
+                None => (None, None)
+            })
+            .collect()
+    }
+
+    // (hidden, shown), both empty for an empty column
+    pub fn tableau_split(&self, index: usize) -> (&[Card], &[Card]) {
+        let tableau = &self.tableaux[index];
+        let first_shown = tableau.cards.len().saturating_sub(tableau.shown_cards as usize);
+        tableau.cards.split_at(first_shown)
+    }
+
+    // Face-up cards that have to move off a card before it's free, nearest first.
+    // Empty for cards outside the tableaux or already on top
+    pub fn blockers_of(&self, card_id: u8) -> Vec<u8> {
+        let Some((t, i)) = self.find_in_tableaux(card_id) else { return vec![] };
+        let tableau = &self.tableaux[t];
+        let first_shown = tableau.cards.len() - tableau.shown_cards as usize;
+        tableau.cards[(i + 1).max(first_shown)..].iter().map(|card| card.value).collect()
+    }
+
+    // Face-down cards on top of a buried card, which can't be named to the player
+    pub fn hidden_blockers_of(&self, card_id: u8) -> usize {
+        let Some((t, i)) = self.find_in_tableaux(card_id) else { return 0 };
+        let tableau = &self.tableaux[t];
+        let first_shown = tableau.cards.len() - tableau.shown_cards as usize;
+        first_shown.saturating_sub(i + 1)
+    }
+
+    fn find_in_tableaux(&self, card_id: u8) -> Option<(usize, usize)> {
+        self.tableaux.iter().enumerate().find_map(|(t, tableau)| {
+            tableau.cards.iter()
+                .position(|card| card.value == card_id)
+                .map(|i| (t, i))
+        })
+    }
+
+    pub fn is_won(&self) -> bool {
+        self.foundations.iter().all(|foundation| foundation.cards.len() == 13)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.is_won()
+    }
+
+    // Called after a card goes up, the 13th card on a foundation finishes its suit
+    pub(crate) fn check_foundation_complete(&mut self, f: usize) {
+        let foundation = &self.foundations[f];
+        if foundation.cards.len() == 13 {
+            let suit = foundation.cards[0].suit;
+            self.emit(GameEvent::FoundationComplete { suit });
+        }
+    }
+
+    // Only the move that finishes the game counts, 'Won' is sent once per win
+    pub(crate) fn check_win(&mut self) {
+        if self.winning_move.is_some() || !self.is_won() { return; }
+        self.winning_move = self.history.last().map(|record| record.mv);
+        self.emit(GameEvent::Won);
+        if self.settings.win_cascade {
+            self.start_cascade();
+        }
+    }
+
+    // Tops first, a card from each foundation in turn
+    fn start_cascade(&mut self) {
+        let mut launches = vec![];
+        for depth in 0..13 {
+            for foundation in &self.foundations {
+                if let Some(card) = foundation.cards.get(depth) {
+                    launches.push((card.value, foundation.quad.pos));
+                }
+            }
+        }
+        let bounds = Vec2::new(self.viewport.x as f32, self.viewport.y as f32);
+        self.cascade = Some(WinCascade::new(launches, self.card_size(), bounds));
+    }
+
+    pub fn winning_move(&self) -> Option<Move> {
+        self.winning_move
+    }
+
+    // Cards played to the foundations so far
+    pub fn foundation_progress(&self) -> usize {
+        self.foundations.iter().map(|foundation| foundation.cards.len()).sum()
+    }
+
+    // Every card in the game, thirteen for each foundation
+    pub fn card_count(&self) -> usize {
+        self.foundations.len() * 13
+    }
+
+    pub fn progress_percent(&self) -> f32 {
+        self.foundation_progress() as f32 / self.card_count() as f32 * 100.0
+    }
+
+    pub fn hidden_card_count(&self) -> usize {
+        self.tableaux.iter()
+            .map(|tableau| tableau.cards.len() - tableau.shown_cards as usize)
+            .sum()
+    }
+
+    // Every face up card, the stock and face down tableau cards are left out
+    pub fn visible_cards(&self) -> Vec<&Card> {
+        let talon_shown = self.talon_shown.max(1).min(self.talon.cards.len());
+        let mut cards: Vec<&Card> = self.talon.cards[..talon_shown].iter().collect();
+        for tableau in &self.tableaux {
+            cards.extend(&tableau.cards[tableau.cards.len() - tableau.shown_cards as usize..]);
+        }
+        for foundation in &self.foundations {
+            cards.extend(&foundation.cards);
+        }
+        cards.extend(&self.hand.cards);
+        cards
+    }
+
+    // One rank lower, and whatever 'tableau_rule' asks of the suits
+    pub(crate) fn can_place_on_tableau(&self, tableau: &Card, hand: &Card) -> bool {
+        let suits_fit = match self.rules.tableau_rule {
+            TableauRule::AlternatingColor => tableau.color != hand.color,
+            TableauRule::AnyColor => true,
+            TableauRule::SameSuit => tableau.suit == hand.suit
+        };
+        suits_fit && tableau.rank == hand.rank + 1
+    }
+
+    // Each card could go on the one before it, so the cards could be moved together
+    pub fn is_valid_run(&self, cards: &[Card]) -> bool {
+        cards.windows(2).all(|pair| self.can_place_on_tableau(&pair[0], &pair[1]))
+    }
+
+    // Cards at the face-up end of a column that form a run, 0 for an empty column
+    pub fn longest_run(&self, tableau: usize) -> usize {
+        let (_, shown) = self.tableau_split(tableau);
+        (1..=shown.len()).rev()
+            .find(|len| self.is_valid_run(&shown[shown.len() - len..]))
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn tableau_accepts(&self, tableau: &Tableau, hand: &Card) -> bool {
+        match tableau.cards.last() {
+            Some(card) => self.can_place_on_tableau(card, hand),
+            None => self.empty_column_accepts(hand)
+        }
+    }
+
+    // Only a King may start an empty column, except in FreeCell where any card can
+    pub(crate) fn empty_column_accepts(&self, hand: &Card) -> bool {
+        match self.rules.variant {
+            GameVariant::FreeCell { .. } => true,
+            GameVariant::Klondike | GameVariant::Thoughtful => hand.rank == 12
+        }
+    }
+
+    // Foundations start from an Ace and build up, or from a King and build down
+    pub(crate) fn can_place_on_foundation(&self, foundation: &Stack, hand: &Card) -> bool {
+        let ascending = self.rules.foundation_ascending;
+        let foundation_size = foundation.cards.len();
+        if foundation_size == 0 {
+            return hand.rank == if ascending { 0 } else { 12 };
+        }
+        let foundation_card = &foundation.cards[0];
+        let next_rank = if ascending { foundation_card.rank + 1 } else { foundation_card.rank.wrapping_sub(1) };
+        if foundation_card.suit == hand.suit && next_rank == hand.rank { return true }
+        false
+    }
+
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        // Any real input stops the demo and restarts the wait for it
+        if matches!(event, WindowEvent::MouseInput { .. } | WindowEvent::CursorMoved { .. } | WindowEvent::KeyboardInput { .. } | WindowEvent::Touch(_)) {
+            self.idle_time = 0.0;
+            self.demo_played_at = 0.0;
+            self.demo_stock_moves = 0;
+        }
+
+        match event {
+            WindowEvent::MouseInput { 
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if self.settings.drag_and_drop && self.hand.cards.is_empty() {
+                    self.press_pos = Some(self.mouse_pos);
+                } else {
+                    self.left_click();
+                }
+                true
+            }
+            WindowEvent::MouseInput { 
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if self.press_pos.take().is_none() { return false; }
+                if self.dragging {
+                    self.dragging = false;
+                    self.drop_hand();
+                } else {
+                    self.left_click();
+                }
+                true
+            }
+            WindowEvent::MouseInput { 
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => {
+                self.return_card();
+                true
+            }
+            WindowEvent::CursorMoved {
+                position,
+                ..
+            } => {
+                self.mouse_pos = self.window_to_world(position);
+                self.hovered_tableau = self.tableaux.iter()
+                    .position(|tableau| tableau.card_quads.iter().any(|quad| quad.contains(self.mouse_pos)));
+                self.start_drag();
+                true
+            }
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => {
+                match self.key_bindings.action(*key) {
+                    Some(action) => {
+                        self.perform(action);
+                        true
+                    },
+                    None => false
+                }
+            }
+            WindowEvent::Touch(touch) => {
+                self.touch(touch);
+                true
+            }
+            WindowEvent::Focused(focused) => {
+                if !self.settings.auto_pause_on_unfocus { return false; }
+                // Freezing with cards in hand would leave them stuck mid drag
+                if !focused && self.hand.cards.is_empty() {
+                    self.pause();
+                } else if *focused && self.paused {
+                    self.resume();
+                }
+                true
+            }
+            _ => { 
+                false
+            }
+        }
+    }
+
+    fn left_click(&mut self) {
+        if self.register_click() {
+            self.double_click();
+        } else {
+            let was_holding = !self.hand.cards.is_empty();
+            self.mouse_click();
+            // Only a click that picked a card up can start a double click,
+            // a quick second click on the stock is just another draw
+            if was_holding || self.hand.cards.is_empty() {
+                self.last_click = None;
+            }
+        }
+    }
+
+    // Picks up the card the button went down on once the cursor has moved far enough.
+    // Moving off anything that can be picked up cancels the press
+    fn start_drag(&mut self) {
+        let Some(press_pos) = self.press_pos else { return };
+        let moved_px = (self.mouse_pos - press_pos).length() as f64 / WORLD_UNITS_PER_PIXEL;
+        if self.dragging || moved_px <= self.settings.drag_threshold_px as f64 { return; }
+
+        let cursor = self.mouse_pos;
+        self.mouse_pos = press_pos;
+        if self.card_under_mouse().is_some() {
+            self.mouse_click();
+            self.dragging = !self.hand.cards.is_empty();
+        }
+        self.mouse_pos = cursor;
+
+        if !self.dragging {
+            self.press_pos = None;
+        }
+    }
+
+    // True when this click completes a double click, the click after one starts afresh
+    fn register_click(&mut self) -> bool {
+        let double = match self.last_click {
+            Some((at, pos)) => {
+                self.game_time - at <= Duration::from_millis(self.double_click_ms)
+                    && (self.mouse_pos - pos).length() <= DOUBLE_CLICK_RADIUS
+            },
+            None => false
+        };
+        self.last_click = if double { None } else { Some((self.game_time, self.mouse_pos)) };
+        double
+    }
+
+    // The first click picked the card up, so it goes back before being sent where it fits
+    fn double_click(&mut self) {
+        self.return_card();
+        let Some((pile, card_index)) = self.card_under_mouse() else { return };
+        let Some(mv) = self.suggested_move_for(pile, card_index) else { return };
+
+        let (values, from) = self.moved_cards(&mv);
+        if self.apply_move(&mv).is_ok() {
+            self.snap_cards(&values, from);
+            self.play_place_sound();
+        }
+    }
+
+    // The face-up card a click would pick up
+    fn card_under_mouse(&self) -> Option<(PileRef, usize)> {
+        if !self.talon.cards.is_empty() && self.talon_top_quad().contains(self.mouse_pos) {
+            return Some((PileRef::Talon, 0));
+        }
+        for (t, tableau) in self.tableaux.iter().enumerate() {
+            let first_shown = tableau.cards.len() - tableau.shown_cards as usize;
+            let hit = (first_shown..tableau.cards.len()).rev()
+                .find(|i| tableau.card_quads[*i].contains(self.mouse_pos));
+            if let Some(i) = hit {
+                if self.is_settling(&tableau.cards[i]) {
+                    return None;
+                }
+                return Some((PileRef::Tableau(t), i));
+            }
+        }
+        self.foundations.iter()
+            .position(|foundation| !foundation.cards.is_empty() && foundation.quad.contains(self.mouse_pos))
+            .map(|f| (PileRef::Foundation(f), 0))
+    }
+
+    // A copy of the face-up card under the cursor, for showing it enlarged. The stock only
+    // counts when 'peek_stock' has its next card face up
+    pub fn preview_under_cursor(&self) -> Option<Card> {
+        if let Some((pile, i)) = self.card_under_mouse() {
+            return self.pile(pile).cards().get(i).cloned();
+        }
+        if self.rules.peek_stock && self.stock.quad.contains(self.mouse_pos) {
+            return self.stock.cards.last().cloned();
+        }
+        None
+    }
+
+    pub fn perform(&mut self, action: Action) {
+        match action {
+            // Does nothing with an empty hand
+            Action::ReturnCard => self.return_card(),
+            Action::CycleTalon => self.cycle_talon(),
+            Action::Undo => {
+                if let Err(e) = self.undo() {
+                    log::info!("{}", e);
+                }
+            },
+            Action::PlayTalon => {
+                self.play_talon_top();
+            },
+            Action::Hint => match self.next_hint() {
+                Some(mv) => log::info!("Hint: {:?}", mv),
+                None => log::info!("No moves left")
+            }
+        }
+    }
+
+    // Only the first finger down plays, any others are ignored until it lifts
+    fn touch(&mut self, touch: &Touch) {
+        match touch.phase {
+            TouchPhase::Started => {
+                if self.active_touch.is_some() { return; }
+                self.active_touch = Some(touch.id);
+                self.mouse_pos = self.window_to_world(&touch.location);
+                if self.hand.cards.is_empty() {
+                    self.mouse_click();
+                    self.hand.quad.pos = self.mouse_pos;
+                }
+            },
+            TouchPhase::Moved => {
+                if self.active_touch != Some(touch.id) { return; }
+                self.mouse_pos = self.window_to_world(&touch.location);
+            },
+            TouchPhase::Ended => {
+                if self.active_touch != Some(touch.id) { return; }
+                self.active_touch = None;
+                self.mouse_pos = self.window_to_world(&touch.location);
+                self.drop_hand();
+            },
+            TouchPhase::Cancelled => {
+                if self.active_touch != Some(touch.id) { return; }
+                self.active_touch = None;
+                self.return_card();
+            }
+        }
+    }
+
+    // Places the held cards under the cursor, sending them back where they came from if they don't fit
+    pub fn drop_hand(&mut self) {
+        if self.hand.cards.is_empty() { return; }
+        self.mouse_click();
+        self.return_card();
+    }
+
+    fn window_to_world(&self, position: &PhysicalPosition<f64>) -> Vec2 {
+        Vec2::new((position.x - (self.viewport.x as f32 / 2.0) as f64) * WORLD_UNITS_PER_PIXEL, -(position.y - (self.viewport.y as f32 / 2.0) as f64) * WORLD_UNITS_PER_PIXEL)
+    }
+
+    fn world_to_window(&self, pos: Vec2) -> Vec2 {
+        Vec2::new(
+            (pos.x as f64 / WORLD_UNITS_PER_PIXEL) as f32 + self.viewport.x as f32 / 2.0,
+            (-pos.y as f64 / WORLD_UNITS_PER_PIXEL) as f32 + self.viewport.y as f32 / 2.0
+        )
+    }
+
+    // Centre of a card in window pixels, for drawing something over it. Indices are into
+    // the pile's 'cards', talon cards under the fanned ones sit where the talon is
+    pub fn card_screen_pos(&self, pile: PileRef, card_index: usize) -> Option<Vec2> {
+        let pos = match pile {
+            PileRef::Stock if card_index < self.stock.cards.len() => self.stock.quad.pos,
+            PileRef::Talon if card_index < self.talon.cards.len() => {
+                let shown = self.talon_shown.max(1).min(self.talon.cards.len());
+                let fanned = shown.saturating_sub(card_index + 1);
+                Vec2::new(self.talon.quad.pos.x + fanned as f32 * self.layout.talon_fan_offset, self.talon.quad.pos.y)
+            },
+            PileRef::Foundation(f) if card_index < self.foundations.get(f)?.cards.len() => self.foundations[f].quad.pos,
+            PileRef::Tableau(t) if card_index < self.tableaux.get(t)?.cards.len() => self.tableaux[t].card_quads[card_index].pos,
+            _ => return None
+        };
+        Some(self.world_to_window(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(seed: u64) -> GameState {
+        GameState::new_with_seed(Audio::silent(), Rules::default(), seed)
+    }
+
+    #[test]
+    fn initial_deal_hides_all_but_the_column_tops() {
+        let game = game(0);
+        let dealt: usize = game.tableaux.iter().map(|tableau| tableau.cards.len()).sum();
+        assert_eq!(dealt, 28);
+        assert_eq!(game.hidden_card_count(), 21);
+    }
+
+    #[test]
+    fn hidden_card_count_drops_as_cards_turn_over() {
+        let mut game = game(0);
+        game.tableaux[6].shown_cards = 3;
+        assert_eq!(game.hidden_card_count(), 19);
+    }
+
+    fn free_cell(free_cells: usize) -> GameState {
+        let rules = Rules { variant: GameVariant::FreeCell { free_cells }, ..Rules::default() };
+        GameState::new_with_seed(Audio::silent(), rules, 0)
+    }
+
+    fn empty_column(game: &mut GameState, t: usize) {
+        game.tableaux[t].cards.clear();
+        game.tableaux[t].shown_cards = 0;
+        game.tableaux[t].calculate_card_quads();
+    }
+
+    // Ends the last column with an A♥ on a 2♠ and clicks the part of the 2♠ left showing
+    fn click_ace_on_two(game: &mut GameState) {
+        game.tableaux[6].cards.truncate(5);
+        game.tableaux[6].cards.extend([Card::new(1), Card::new(13)]);
+        game.tableaux[6].shown_cards = 2;
+        game.tableaux[6].calculate_card_quads();
+        let under = game.tableaux[6].card_quads[5].pos;
+        game.mouse_pos = Vec2::new(under.x, under.y + 100.0);
+        game.mouse_click();
+    }
+
+    #[test]
+    fn klondike_moves_runs_of_any_length() {
+        assert_eq!(game(0).max_movable(), usize::MAX);
+    }
+
+    #[test]
+    fn free_cell_moves_one_more_than_its_free_cells() {
+        assert_eq!(free_cell(0).max_movable(), 1);
+        assert_eq!(free_cell(4).max_movable(), 5);
+    }
+
+    #[test]
+    fn each_empty_column_doubles_what_free_cell_moves() {
+        let mut game = free_cell(4);
+        empty_column(&mut game, 0);
+        assert_eq!(game.max_movable(), 10);
+        empty_column(&mut game, 1);
+        assert_eq!(game.max_movable(), 20);
+    }
+
+    #[test]
+    fn free_cell_refuses_to_pick_up_too_long_a_run() {
+        // A run of two needs a free cell
+        let click_run = |free_cells: usize| {
+            let mut game = free_cell(free_cells);
+            click_ace_on_two(&mut game);
+            game.hand.cards.len()
+        };
+        assert_eq!(click_run(0), 0);
+        assert_eq!(click_run(1), 2);
+    }
+
+    #[test]
+    fn a_column_showing_nothing_gets_its_top_turned_over() {
+        let mut game = game(0);
+        game.tableaux[3].shown_cards = 0;
+        game.ensure_top_revealed();
+        assert_eq!(game.tableaux[3].shown_cards, 1);
+
+        // Clickable again, so the column isn't stuck
+        game.mouse_pos = game.tableaux[3].card_quads[3].pos;
+        game.mouse_click();
+        assert_eq!(game.hand.cards.len(), 1);
+    }
+
+    #[test]
+    fn empty_columns_and_shown_tops_are_left_alone() {
+        let mut game = game(0);
+        empty_column(&mut game, 0);
+        game.ensure_top_revealed();
+        let shown: Vec<u8> = game.tableaux.iter().map(|tableau| tableau.shown_cards).collect();
+        assert_eq!(shown, [0, 1, 1, 1, 1, 1, 1]);
+    }
+
+    fn draw_three(seed: u64) -> GameState {
+        let rules = Rules { draw_count: 3, ..Rules::default() };
+        GameState::new_with_seed(Audio::silent(), rules, seed)
+    }
+
+    fn click_stock(game: &mut GameState) {
+        game.mouse_pos = game.stock.quad.pos;
+        game.mouse_click();
+    }
+
+    fn click_talon(game: &mut GameState) {
+        game.mouse_pos = game.talon_top_quad().pos;
+        game.mouse_click();
+    }
+
+    #[test]
+    fn cancelling_a_talon_pick_up_keeps_the_whole_draw_fanned() {
+        let mut game = draw_three(0);
+        click_stock(&mut game);
+        assert_eq!(game.talon_shown, 3);
+
+        click_talon(&mut game);
+        assert_eq!(game.hand.cards.len(), 1);
+        game.return_card();
+        assert_eq!(game.talon_shown, 3);
+        assert_eq!(game.talon.cards.len(), 3);
+    }
+
+    #[test]
+    fn cancelling_a_talon_pick_up_doesnt_fan_out_more_cards() {
+        let mut game = draw_three(0);
+        click_stock(&mut game);
+        // Whatever was on top has been played, two of the draw are left showing
+        game.take_talon_top();
+        assert_eq!(game.talon_shown, 2);
+
+        click_talon(&mut game);
+        game.return_card();
+        assert_eq!(game.talon_shown, 2);
+    }
+
+    fn deck_values(deck: &Stack) -> Vec<u8> {
+        deck.cards.iter().map(|card| card.value).collect()
+    }
+
+    #[test]
+    fn a_seed_always_shuffles_the_same_way() {
+        assert_eq!(deck_values(&Stack::seeded_deck(42)), deck_values(&Stack::seeded_deck(42)));
+        assert_ne!(deck_values(&Stack::seeded_deck(42)), deck_values(&Stack::seeded_deck(43)));
+    }
+
+    #[test]
+    fn a_shuffled_deck_has_every_card_once() {
+        let mut values = deck_values(&Stack::seeded_deck(7));
+        values.sort();
+        assert_eq!(values, (0..52).collect::<Vec<u8>>());
+    }
+
+    fn foundation_of(values: &[u8]) -> Stack {
+        let mut foundation = Stack::empty();
+        foundation.push(values.iter().map(|value| Card::new(*value)).collect());
+        foundation
+    }
+
+    #[test]
+    fn ascending_foundations_start_from_an_ace_and_build_up_in_suit() {
+        let game = game(0);
+        let empty = Stack::empty();
+        assert!(game.can_place_on_foundation(&empty, &Card::new(0)));
+        assert!(!game.can_place_on_foundation(&empty, &Card::new(12)));
+
+        // A♠, 2♠
+        let spades = foundation_of(&[0, 1]);
+        assert!(game.can_place_on_foundation(&spades, &Card::new(2)));
+        assert!(!game.can_place_on_foundation(&spades, &Card::new(0)));
+        assert!(!game.can_place_on_foundation(&spades, &Card::new(15)));
+    }
+
+    #[test]
+    fn descending_foundations_start_from_a_king_and_build_down_in_suit() {
+        let rules = Rules { foundation_ascending: false, ..Rules::default() };
+        let game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        let empty = Stack::empty();
+        assert!(game.can_place_on_foundation(&empty, &Card::new(12)));
+        assert!(!game.can_place_on_foundation(&empty, &Card::new(0)));
+
+        // K♠, Q♠
+        let spades = foundation_of(&[12, 11]);
+        assert!(game.can_place_on_foundation(&spades, &Card::new(10)));
+        assert!(!game.can_place_on_foundation(&spades, &Card::new(12)));
+        assert!(!game.can_place_on_foundation(&spades, &Card::new(23)));
+    }
+
+    // A click on the talon top, then another after 'gap_ms' on the same spot
+    fn click_talon_twice(gap_ms: u64) -> GameState {
+        let mut game = game(0);
+        game.apply_move(&Move::FlipStock).unwrap();
+        game.mouse_pos = game.talon_top_quad().pos;
+        game.left_click();
+        assert_eq!(game.hand.cards.len(), 1);
+
+        game.game_time += Duration::from_millis(gap_ms);
+        game.left_click();
+        game
+    }
+
+    #[test]
+    fn a_second_click_within_double_click_ms_is_a_double_click() {
+        // The card goes back and is then sent wherever it fits, so nothing is left held
+        let game = click_talon_twice(300);
+        assert_eq!(game.hand.cards.len(), 0);
+    }
+
+    #[test]
+    fn a_second_click_after_double_click_ms_is_a_single_click() {
+        // Clicking the talon again with a card held doesn't put it down
+        let game = click_talon_twice(301);
+        assert_eq!(game.hand.cards.len(), 1);
+    }
+
+    #[test]
+    fn a_second_click_too_far_away_is_a_single_click() {
+        let mut game = game(0);
+        game.apply_move(&Move::FlipStock).unwrap();
+        game.mouse_pos = game.talon_top_quad().pos;
+        game.left_click();
+        game.mouse_pos += Vec2::new(DOUBLE_CLICK_RADIUS + 1.0, 0.0);
+        game.left_click();
+        assert_eq!(game.hand.cards.len(), 1);
+    }
+
+    #[test]
+    fn quick_clicks_on_the_stock_each_draw() {
+        let mut game = game(0);
+        game.mouse_pos = game.stock.quad.pos;
+        game.left_click();
+        game.game_time += Duration::from_millis(100);
+        game.left_click();
+        assert_eq!(game.talon.cards.len(), 2);
+    }
+
+    fn shown_counts(game: &GameState) -> Vec<u8> {
+        game.tableaux.iter().map(|tableau| tableau.shown_cards).collect()
+    }
+
+    #[test]
+    fn initial_shown_turns_up_that_many_cards_on_each_column() {
+        let rules = Rules { initial_shown: 2, ..Rules::default() };
+        let game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        // The first column only has the one card
+        assert_eq!(shown_counts(&game), [1, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(game.hidden_card_count(), 15);
+    }
+
+    #[test]
+    fn cards_dealt_face_up_arent_turned_over_again() {
+        let rules = Rules { initial_shown: 2, ..Rules::default() };
+        let mut game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        // The 8♣ onto the 9♥ leaves the 10♠ under it showing, nothing new turns over
+        game.apply_move(&Move::TableauToTableau { from: 4, start: 4, to: 3 }).unwrap();
+        assert_eq!(game.tableaux[4].shown_cards, 1);
+        assert_eq!(game.hidden_card_count(), 15);
+    }
+
+    #[test]
+    fn clicking_face_up_cards_that_arent_a_run_picks_nothing_up() {
+        let rules = Rules { variant: GameVariant::Thoughtful, ..Rules::default() };
+        let mut game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        // The A♥ of the fourth column, under the 9♥
+        let under = game.tableaux[3].card_quads[2].pos;
+        game.mouse_pos = Vec2::new(under.x, under.y + 100.0);
+        game.mouse_click();
+        assert_eq!(game.hand.cards.len(), 0);
+        assert_eq!(game.tableaux[3].cards.len(), 4);
+    }
+
+    fn set_column(game: &mut GameState, t: usize, values: &[u8], shown: u8) {
+        game.tableaux[t].cards = values.iter().map(|value| Card::new(*value)).collect();
+        game.tableaux[t].shown_cards = shown;
+        game.tableaux[t].calculate_card_quads();
+    }
+
+    #[test]
+    fn longest_run_stops_where_the_sequence_breaks() {
+        let mut game = game(0);
+        // K♥ then 9♠, 8♥, 7♣
+        set_column(&mut game, 0, &[25, 8, 20, 32], 4);
+        assert_eq!(game.longest_run(0), 3);
+    }
+
+    #[test]
+    fn longest_run_covers_a_fully_ordered_column() {
+        let mut game = game(0);
+        // 10♥, 9♠, 8♥
+        set_column(&mut game, 0, &[22, 8, 20], 3);
+        assert_eq!(game.longest_run(0), 3);
+        // Only face-up cards count
+        set_column(&mut game, 0, &[22, 8, 20], 2);
+        assert_eq!(game.longest_run(0), 2);
+    }
+
+    #[test]
+    fn longest_run_of_an_empty_column_is_zero() {
+        let mut game = game(0);
+        empty_column(&mut game, 0);
+        assert_eq!(game.longest_run(0), 0);
+    }
+
+    #[test]
+    fn a_held_run_is_refused_by_the_foundations() {
+        let mut game = game(0);
+        click_ace_on_two(&mut game);
+        assert_eq!(game.hand.cards.len(), 2);
+        game.drain_events();
+
+        game.mouse_pos = game.foundations[0].quad.pos;
+        game.mouse_click();
+        assert_eq!(game.hand.cards.len(), 2);
+        assert_eq!(game.foundations[0].cards.len(), 0);
+        assert_eq!(game.drain_events(), [GameEvent::InvalidMove { reason: InvalidReason::RunOnFoundation }]);
+        assert_eq!(game.foundation_flash(), Some(0));
+        assert_eq!(game.mistakes(), 1);
+        assert!(game.history.is_empty());
+    }
+
+    #[test]
+    fn aces_dealt_on_top_go_straight_up_with_autoplay_aces() {
+        let rules = Rules { autoplay_aces: true, ..Rules::default() };
+        let game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        // Seed 0 deals the A♦ on top of the last column, the 5♥ under it turns over
+        assert_eq!(game.foundation_progress(), 1);
+        assert_eq!(game.foundations[0].cards[0].to_string(), "Ad");
+        assert_eq!(game.tableaux[6].cards.len(), 6);
+        assert_eq!(game.tableaux[6].shown_cards, 1);
+        assert!(game.tableaux.iter().all(|tableau| tableau.cards.last().is_none_or(|card| card.rank != 0)));
+        // Part of the deal, not a move
+        assert!(game.history.is_empty());
+        assert_eq!(game.score(), 0);
+    }
+
+    #[test]
+    fn aces_stay_put_without_autoplay_aces() {
+        let game = game(0);
+        assert_eq!(game.foundation_progress(), 0);
+        assert_eq!(game.tableaux[6].cards[6].to_string(), "Ad");
+    }
+
+    #[test]
+    fn dropping_a_run_back_on_its_column_only_cancels_the_pick_up() {
+        let mut game = game(0);
+        click_ace_on_two(&mut game);
+        assert_eq!(game.hand.cards.len(), 2);
+        assert_eq!(game.tableaux[6].shown_cards, 0);
+
+        // The 2♠ wouldn't fit on the 7♠ now on top, it still goes back
+        game.mouse_pos = game.tableaux[6].card_quads[4].pos;
+        game.mouse_click();
+        assert_eq!(game.hand.cards.len(), 0);
+        assert_eq!(game.tableaux[6].cards.len(), 7);
+        assert_eq!(game.tableaux[6].shown_cards, 2);
+        assert_eq!(game.hidden_card_count(), 20);
+        assert!(game.history.is_empty());
+        assert_eq!(game.mistakes(), 0);
+    }
+
+    #[test]
+    fn recycling_turns_the_talon_back_into_the_same_stock() {
+        let mut game = game(0);
+        let stock = deck_values(&game.stock);
+        while !game.stock.cards.is_empty() {
+            game.apply_move(&Move::FlipStock).unwrap();
+        }
+        game.drain_events();
+
+        click_stock(&mut game);
+        assert_eq!(deck_values(&game.stock), stock);
+        assert_eq!(game.talon.cards.len(), 0);
+        assert!(game.drain_events().contains(&GameEvent::StockRecycled { cards: 24 }));
+    }
+
+    // Every card up but the K♦, which the deal leaves alone on the first column
+    fn one_card_from_winning() -> GameState {
+        let suit = |suit: u8, ranks: u8| (0..ranks).map(|rank| suit * 13 + rank).collect::<Vec<u8>>();
+        let foundations = vec![suit(0, 13), suit(1, 13), suit(2, 13), suit(3, 12)];
+        GameState::new_with_foundations(Audio::silent(), Rules::default(), 0, foundations).unwrap()
+    }
+
+    #[test]
+    fn the_last_card_up_wins_once() {
+        let mut game = one_card_from_winning();
+        assert_eq!(game.tableaux[0].cards[0].to_string(), "Kd");
+        let winning = Move::TableauToFoundation { from: 0, to: 3 };
+        game.apply_move(&winning).unwrap();
+
+        assert!(game.is_won());
+        assert_eq!(game.winning_move(), Some(winning));
+        let wins = game.drain_events().iter().filter(|event| **event == GameEvent::Won).count();
+        assert_eq!(wins, 1);
+
+        game.check_win();
+        assert!(!game.drain_events().contains(&GameEvent::Won));
+    }
+
+    #[test]
+    fn a_king_as_the_thirteenth_card_completes_its_suit() {
+        let mut game = one_card_from_winning();
+        game.apply_move(&Move::TableauToFoundation { from: 0, to: 3 }).unwrap();
+        assert!(game.drain_events().contains(&GameEvent::FoundationComplete { suit: Suit::Diamond }));
+    }
+
+    #[test]
+    fn a_foundation_short_of_its_king_isnt_complete() {
+        let mut game = game(0);
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        let completed = game.drain_events().iter().any(|event| matches!(event, GameEvent::FoundationComplete { .. }));
+        assert!(!completed);
+    }
+
+    fn card_total(game: &GameState) -> usize {
+        game.stock.cards.len() + game.talon.cards.len() + game.hand.cards.len()
+            + game.foundations.iter().map(|foundation| foundation.cards.len()).sum::<usize>()
+            + game.tableaux.iter().map(|tableau| tableau.cards.len()).sum::<usize>()
+    }
+
+    // Seed 0 laid out for two decks, eight foundations and another deck shuffled into the stock
+    fn double_deck() -> GameState {
+        let mut game = game(0);
+        game.foundations = GameState::create_foundations(&game.layout, 8);
+        game.stock.cards.extend(Stack::seeded_deck(1).cards);
+        game
+    }
+
+    #[test]
+    fn two_decks_put_up_two_aces_of_a_suit() {
+        let mut game = double_deck();
+        let (ace, two) = (Card::new(0), Card::new(1));
+        game.foundations[0].cards.insert(0, ace.clone());
+        assert!(game.can_place_on_foundation(&game.foundations[0], &two));
+        assert!(!game.can_place_on_foundation(&game.foundations[0], &ace));
+        assert_eq!(game.target_foundation(&ace), Some(1));
+
+        game.foundations[1].cards.insert(0, ace);
+        game.foundations[0].cards.insert(0, two.clone());
+        assert!(game.can_place_on_foundation(&game.foundations[1], &two));
+        assert_eq!(game.target_foundation(&two), Some(1));
+    }
+
+    #[test]
+    fn two_decks_count_progress_out_of_every_card() {
+        let mut game = double_deck();
+        assert_eq!(game.card_count(), card_total(&game));
+        game.foundations[0].cards = (0..13).rev().map(Card::new).collect();
+        assert_eq!(game.progress_percent(), 12.5);
+    }
+
+    #[test]
+    fn clear_hand_puts_cards_back_where_they_came_from() {
+        let mut game = game(0);
+        let card = game.tableaux[6].cards.pop().unwrap();
+        game.tableaux[6].shown_cards -= 1;
+        game.hand.cards.push(card);
+        game.hand_origin = PileRef::Tableau(6);
+
+        game.clear_hand();
+        assert_eq!(game.hand.cards.len(), 0);
+        assert_eq!(game.tableaux[6].cards.len(), 7);
+        assert_eq!(card_total(&game), 52);
+    }
+
+    #[test]
+    fn clear_hand_falls_back_to_the_first_column() {
+        let mut game = game(0);
+        game.hand.cards = game.stock.cards.drain(..2).collect();
+        // Two cards can't have come off the talon
+        game.hand_origin = PileRef::Talon;
+
+        game.clear_hand();
+        assert_eq!(game.hand.cards.len(), 0);
+        assert_eq!(game.tableaux[0].cards.len(), 3);
+        assert_eq!(card_total(&game), 52);
+    }
+
+    #[test]
+    fn clear_hand_falls_back_from_a_foundation_that_doesnt_exist() {
+        let mut game = game(0);
+        game.hand.cards = game.stock.cards.drain(..1).collect();
+        game.hand_origin = PileRef::Foundation(9);
+
+        game.clear_hand();
+        assert_eq!(game.hand.cards.len(), 0);
+        assert_eq!(game.tableaux[0].cards.len(), 2);
+        assert_eq!(card_total(&game), 52);
+    }
+
+    // Seed 0 with its A♦ played to the first foundation, the 2♠ it could come back to on the second column
+    fn ace_on_foundation() -> GameState {
+        let mut game = game(0);
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        game.score = 100;
+        game.mouse_pos = game.foundations[0].quad.pos;
+        game.mouse_click();
+        assert_eq!(game.hand.cards[0].to_string(), "Ad");
+        game
+    }
+
+    #[test]
+    fn a_foundation_card_dropped_on_a_column_loses_points() {
+        let mut game = ace_on_foundation();
+        game.mouse_pos = game.tableaux[1].card_quads.last().unwrap().pos;
+        game.mouse_click();
+        assert_eq!(game.tableaux[1].cards.last().unwrap().to_string(), "Ad");
+        assert_eq!(game.foundations[0].cards.len(), 0);
+        assert_eq!(game.score(), 85);
+    }
+
+    #[test]
+    fn a_cancelled_foundation_pick_up_keeps_the_score() {
+        let mut game = ace_on_foundation();
+        game.return_card();
+        assert_eq!(game.foundations[0].cards[0].to_string(), "Ad");
+        assert_eq!(game.score(), 100);
+    }
+
+    // Whether the 5♥ goes on the 6♦ and on the 6♥ under a tableau rule
+    fn red_on_red(tableau_rule: TableauRule) -> (bool, bool) {
+        let rules = Rules { tableau_rule, ..Rules::default() };
+        let game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        let five_hearts = Card::new(17);
+        (game.can_place_on_tableau(&Card::new(44), &five_hearts), game.can_place_on_tableau(&Card::new(18), &five_hearts))
+    }
+
+    #[test]
+    fn alternating_color_turns_away_red_on_red() {
+        assert_eq!(red_on_red(TableauRule::AlternatingColor), (false, false));
+    }
+
+    #[test]
+    fn any_color_takes_red_on_red() {
+        assert_eq!(red_on_red(TableauRule::AnyColor), (true, true));
+    }
+
+    #[test]
+    fn same_suit_only_takes_its_own_suit() {
+        assert_eq!(red_on_red(TableauRule::SameSuit), (false, true));
+    }
+
+    fn scored(scoring: ScoringMode) -> GameState {
+        GameState::new_with_seed(Audio::silent(), Rules { scoring, ..Rules::default() }, 0)
+    }
+
+    #[test]
+    fn vegas_starts_at_the_buy_in() {
+        assert_eq!(scored(ScoringMode::Vegas).score(), -52);
+        assert_eq!(scored(ScoringMode::VegasCumulative).score(), -52);
+    }
+
+    #[test]
+    fn vegas_pays_for_each_card_up() {
+        let mut game = scored(ScoringMode::Vegas);
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        assert_eq!(game.score(), -47);
+        game.new_game();
+        assert_eq!(game.score(), -52);
+    }
+
+    #[test]
+    fn cumulative_vegas_carries_the_balance_into_the_next_game() {
+        let mut game = scored(ScoringMode::VegasCumulative);
+        game.apply_move(&Move::TableauToFoundation { from: 6, to: 0 }).unwrap();
+        game.new_game();
+        assert_eq!(game.vegas_balance, -47);
+        assert_eq!(game.foundation_progress(), 0);
+        assert_eq!(game.score(), -99);
+    }
+
+    #[test]
+    fn a_doubled_viewport_doubles_the_board() {
+        let normal = game(0);
+        let doubled = GameState::new_with_viewport(Audio::silent(), SCREEN_SIZE.x * 2, SCREEN_SIZE.y * 2);
+        assert_eq!(doubled.stock.quad.pos, normal.stock.quad.pos * 2.0);
+        assert_eq!(doubled.talon.quad.pos, normal.talon.quad.pos * 2.0);
+        assert_eq!(doubled.card_size(), normal.card_size() * 2.0);
+        for f in 0..normal.foundations.len() {
+            assert_eq!(doubled.foundations[f].quad, Quad::new(normal.foundations[f].quad.pos * 2.0, normal.card_size() * 2.0));
+        }
+        for t in 0..normal.tableaux.len() {
+            let (normal, doubled) = (&normal.tableaux[t], &doubled.tableaux[t]);
+            assert_eq!(doubled.x_position, normal.x_position * 2.0);
+            assert_eq!(doubled.y_position, normal.y_position * 2.0);
+        }
+        // Columns fan out twice as far, whatever the cards dealt
+        let gap = |game: &GameState| game.tableaux[6].card_quads[0].pos.y - game.tableaux[6].card_quads[1].pos.y;
+        assert_eq!(gap(&doubled), gap(&normal) * 2.0);
+        assert_eq!(doubled.layout.talon_fan_offset, TALON_FAN_OFFSET * 2.0);
+        assert_eq!(doubled.layout.pass_dot_spacing, PASS_DOT_SPACING * 2.0);
+    }
+
+    #[test]
+    fn the_empty_stock_does_nothing_without_recycling() {
+        let rules = Rules { recycle_allowed: false, ..Rules::default() };
+        let mut game = GameState::new_with_seed(Audio::silent(), rules, 0);
+        while !game.stock.cards.is_empty() {
+            click_stock(&mut game);
+        }
+        let talon = deck_values(&game.talon);
+        let moves = game.move_timeline().len();
+        assert!(!game.can_flip_stock());
+
+        click_stock(&mut game);
+        assert_eq!(game.stock.cards.len(), 0);
+        assert_eq!(deck_values(&game.talon), talon);
+        assert_eq!(game.move_timeline().len(), moves);
+    }
+
+    fn seed_from_code(code: &str) -> Result<u64, ParseError> {
+        GameState::from_deal_code(code, Audio::silent()).map(|game| game.seed())
+    }
+
+    #[test]
+    fn deal_codes_give_back_their_seed() {
+        for seed in [0, 1, 35, 36, 12345, u64::MAX] {
+            let code = game(seed).deal_code();
+            assert_eq!(seed_from_code(&code), Ok(seed), "{}", code);
+        }
+        assert_eq!(game(0).deal_code(), "0");
+    }
+
+    #[test]
+    fn malformed_deal_codes_are_rejected() {
+        assert_eq!(seed_from_code(""), Err(ParseError::Empty));
+        assert_eq!(seed_from_code("  "), Err(ParseError::Empty));
+        assert_eq!(seed_from_code("12-4"), Err(ParseError::InvalidCharacter('-')));
+        assert_eq!(seed_from_code("deal#1"), Err(ParseError::InvalidCharacter('#')));
+        // One past u64::MAX, which is 3w5e11264sgsf
+        assert_eq!(seed_from_code("3w5e11264sgsg"), Err(ParseError::TooLong));
+    }
+
+    #[test]
+    fn a_fresh_deal_splits_into_hidden_cards_under_one_shown() {
+        let game = game(3);
+        for i in 0..game.tableaux.len() {
+            let (hidden, shown) = game.tableau_split(i);
+            assert_eq!((hidden.len(), shown.len()), (i, 1));
+            assert_eq!(shown[0], game.tableaux[i].cards[i]);
+        }
+    }
+
+    #[test]
+    fn an_empty_column_splits_into_nothing() {
+        let mut game = game(3);
+        empty_column(&mut game, 4);
+        let (hidden, shown) = game.tableau_split(4);
+        assert!(hidden.is_empty());
+        assert!(shown.is_empty());
+    }
 }